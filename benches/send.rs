@@ -0,0 +1,86 @@
+//! Memory allocations and time per command
+//! with `send` and allocation-free `send_into`
+//!
+//! Commands are sent over TCP to an emulated reader
+//! served in another thread, only allocations made
+//! by the sending thread are counted.
+//!
+//! Run with `cargo bench --bench send`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::net::TcpListener;
+use std::time::Instant;
+
+use uem_reader::emulator::*;
+use uem_reader::reader::{UemReaderInternalTrait, tcp::*};
+
+const ITERATIONS: u32 = 10_000;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations() -> u64 {
+    ALLOCATIONS.with(|count| count.get())
+}
+
+fn measure(name: &str, mut command: impl FnMut()) {
+    // Warm up reusable buffers
+    command();
+    let allocated = allocations();
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        command();
+    }
+    let took = started.elapsed();
+    println!("{:<10} {:>8.2} allocations/command {:>10.2?}/command",
+        name,
+        (allocations() - allocated) as f64 / ITERATIONS as f64,
+        took / ITERATIONS);
+}
+
+fn main() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut uem_emulator = UemEmulator::new(UemEmulatorState::default());
+        uem_emulator.serve(stream).ok();
+    });
+
+    let mut uem_reader = new_tcp_reader(&address, &UemTcpParameters::default());
+    uem_reader.open().unwrap();
+
+    measure("send", || {
+        uem_reader.send(&[0x22]).unwrap();
+    });
+
+    let mut serial = [0u8; 16];
+    measure("send_into", || {
+        uem_reader.send_into(&[0x22], &mut serial).unwrap();
+    });
+
+    uem_reader.close().unwrap();
+}
@@ -0,0 +1,136 @@
+//! Virtual MicroEM reader on a pseudo-terminal
+//!
+//! Starts the emulator, prints the terminal path and
+//! serves commands until interrupted. Serial transport
+//! of the library or any other software can use the path
+//! as a regular serial port.
+//!
+//! Usage:
+//!
+//! ```console
+//! uem-emulator [--link PATH] [--address N] [--version HEX] [--serial HEX] [--uid HEX] [--no-card]
+//! ```
+
+#[cfg(unix)]
+fn main() {
+    use std::fs::File;
+    use std::os::unix::io::FromRawFd;
+    use uem_reader::emulator::*;
+
+    let mut state = UemEmulatorState::default();
+    let mut link: Option<String> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage(&arg));
+        match arg.as_str() {
+            "--link" => link = Some(value()),
+            "--address" => state.address = value().parse().unwrap_or_else(|_| usage("--address")),
+            "--version" => state.version = parse_hex(&value()),
+            "--serial" => state.serial = parse_hex(&value()),
+            "--uid" => {
+                let uid = parse_hex(&value());
+                if let Some(card) = state.card.as_mut() {
+                    card.uid = uid;
+                }
+            },
+            "--no-card" => state.card = None,
+            _ => usage(&arg),
+        }
+    }
+
+    let (master, slave) = match open_pty() {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Failed to open pseudo-terminal: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let path = match pty_path(slave) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Failed to get pseudo-terminal name: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(link) = link.as_ref() {
+        std::fs::remove_file(link).ok();
+        if let Err(e) = std::os::unix::fs::symlink(&path, link) {
+            eprintln!("Failed to create link {}: {}", link, e);
+            std::process::exit(1);
+        }
+        println!("{} -> {}", link, path);
+    } else {
+        println!("{}", path);
+    }
+
+    // Slave side stays open, so the master does not
+    // fail while no client has the terminal opened
+    let _slave = unsafe { File::from_raw_fd(slave) };
+    let master = unsafe { File::from_raw_fd(master) };
+
+    let mut uem_emulator = UemEmulator::new(state);
+    if let Err(e) = uem_emulator.serve(master) {
+        eprintln!("Emulator stopped: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(unix)]
+fn open_pty() -> std::io::Result<(i32, i32)> {
+    let mut master: libc::c_int = -1;
+    let mut slave: libc::c_int = -1;
+    let res = unsafe {
+        libc::openpty(&mut master, &mut slave,
+            std::ptr::null_mut(), std::ptr::null_mut(), std::ptr::null_mut())
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // Pass protocol bytes through unchanged
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(slave, &mut termios) == 0 {
+            libc::cfmakeraw(&mut termios);
+            libc::tcsetattr(slave, libc::TCSANOW, &termios);
+        }
+    }
+    Ok((master, slave))
+}
+
+#[cfg(unix)]
+fn pty_path(slave: i32) -> std::io::Result<String> {
+    let mut name = [0 as libc::c_char; 256];
+    let res = unsafe { libc::ttyname_r(slave, name.as_mut_ptr(), name.len()) };
+    if res != 0 {
+        return Err(std::io::Error::from_raw_os_error(res));
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+#[cfg(unix)]
+fn parse_hex(value: &str) -> Vec<u8> {
+    let value = value.trim_start_matches("0x");
+    if !value.len().is_multiple_of(2) {
+        usage(value);
+    }
+    (0..value.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap_or_else(|_| usage(value)))
+        .collect()
+}
+
+#[cfg(unix)]
+fn usage(arg: &str) -> ! {
+    eprintln!("Unexpected argument: {}", arg);
+    eprintln!("Usage: uem-emulator [--link PATH] [--address N] [--version HEX] [--serial HEX] [--uid HEX] [--no-card]");
+    std::process::exit(2);
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("Pseudo-terminals are not supported on this platform");
+    std::process::exit(1);
+}
@@ -3,6 +3,8 @@
 
 #![allow(dead_code)]
 
+use alloc::vec::Vec;
+
 use enum_iterator::Sequence;
 
 // #[repr(u8)]
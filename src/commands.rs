@@ -2,13 +2,22 @@
 
 pub mod reader;
 pub mod cards;
+pub mod session;
+pub mod worker;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+
+use std::cell::RefMut;
+use std::time::Duration;
 
 use crate::reader::*;
-use crate::commands::{reader::*, cards::*};
+use crate::errors::UemError;
+use crate::commands::{reader::*, cards::*, session::*};
 
 /// Structure for grouping commands in general
 pub struct UemCommands<'a> {
-    reader: &'a UemReader,
+    reader: ReaderAccess<'a>,
+    timeout: Option<Duration>,
 }
 
 /// Accessing general commands group
@@ -18,22 +27,109 @@ pub trait UemCommandsTrait {
 
 impl<'a> UemCommandsReaderTrait for UemCommands<'a> {  
     fn reader(&mut self) -> UemCommandsReader {
-        UemCommandsReader::new(self.as_reader())
+        UemCommandsReader::new(self.as_reader(), self.timeout)
     }
 }
 
 impl<'a> UemCommandsCardsTrait for UemCommands<'a> {  
     fn cards(&mut self) -> UemCommandsCards {
-        UemCommandsCards::new(self.as_reader())
+        UemCommandsCards::new(self.as_reader(), self.timeout)
     }
 }
 
 impl<'a> UemCommands<'a> {
     pub(crate) fn new(rd: &'a UemReader) -> Self {
-        UemCommands {reader: rd}
+        UemCommands {reader: ReaderAccess::Shared(rd), timeout: None}
+    }
+
+    pub(crate) fn in_session(session: &'a dyn SessionReader) -> Self {
+        UemCommands {reader: ReaderAccess::Session(session), timeout: None}
     }
     
-    pub(crate) fn as_reader(&self) -> &'a UemReader {
+    pub(crate) fn as_reader(&self) -> ReaderAccess<'a> {
         self.reader
     }
-}
\ No newline at end of file
+
+    /// Override reader response timeout for
+    /// all commands of the group and its subgroups
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// # use std::time::Duration;
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
+    /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x64], &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// let version = uem_reader.commands()
+    ///     .with_timeout(Duration::from_millis(100))
+    ///     .reader()
+    ///     .get_version();
+    /// # assert!(version.is_ok());
+    /// # uem_mock.assert_done();
+    /// ```
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// Way a command group reaches its reader
+#[derive(Clone, Copy)]
+pub(crate) enum ReaderAccess<'a> {
+    /// Reader is locked for every command
+    Shared(&'a UemReader),
+    /// Reader is held by an [exclusive session](UemSession)
+    Session(&'a dyn SessionReader),
+}
+
+/// Reader locked for a single command of a group
+pub(crate) struct LockedReader<'a> {
+    reader: ReaderLock<'a>,
+    timeout: Option<Duration>,
+}
+
+enum ReaderLock<'a> {
+    Shared(ReaderGuard<'a>),
+    Session(RefMut<'a, dyn UemReaderInternalTrait + Send + 'static>),
+}
+
+impl<'a> LockedReader<'a> {
+    pub(crate) fn new(reader: ReaderAccess<'a>, timeout: Option<Duration>) -> core::result::Result<Self, UemError> {
+        let reader = match reader {
+            ReaderAccess::Shared(reader) => ReaderLock::Shared(lock_reader(reader)?),
+            ReaderAccess::Session(session) => ReaderLock::Session(session.borrow_reader()?),
+        };
+        Ok(LockedReader {reader, timeout})
+    }
+
+    fn reader(&mut self) -> &mut (dyn UemReaderInternalTrait + Send + 'static) {
+        match &mut self.reader {
+            ReaderLock::Shared(reader) => &mut **reader,
+            ReaderLock::Session(reader) => &mut **reader,
+        }
+    }
+
+    /// Send a command applying timeout override of the group
+    pub(crate) fn send(&mut self, command: &[u8]) -> UemResultVec {
+        match self.timeout {
+            Some(timeout) => self.reader().send_with_timeout(command, timeout),
+            None => self.reader().send(command),
+        }
+    }
+
+    /// Send a command into a caller-supplied buffer
+    /// applying timeout override of the group
+    pub(crate) fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return self.reader().send_into(command, response),
+        };
+        let default_timeout = self.reader().timeout();
+        self.reader().set_timeout(timeout)?;
+        let res = self.reader().send_into(command, response);
+        self.reader().set_timeout(default_timeout).ok();
+        res
+    }
+}
@@ -0,0 +1,311 @@
+//! Asynchronous command groups
+//!
+//! Available with `async` feature. Groups mirror blocking
+//! [reader](crate::commands::reader), [cards](crate::commands::cards)
+//! and [Mifare Classic](crate::commands::cards::mifare::classic) groups
+//! and are accessed through [`UemReaderAsync::commands`](UemReaderAsync::commands).
+//!
+//! A group timeout, unlike the blocking one, limits
+//! the whole command including waiting for the reader
+//! to become free. Each transaction of the command
+//! is limited by the same timeout on the reader side.
+
+use std::time::Duration;
+
+use crate::reader::{*, asynchronous::*};
+use crate::commands::{*, reader::*, cards::{*, mifare::{*, classic::*}}};
+use crate::card::*;
+
+/// Blocking commands object applying timeout of an asynchronous group
+fn blocking(reader: &UemReader, timeout: Option<Duration>) -> UemCommands<'_> {
+    let commands = UemCommands::new(reader);
+    match timeout {
+        Some(timeout) => commands.with_timeout(timeout),
+        None => commands,
+    }
+}
+
+/// Structure for grouping asynchronous commands in general
+pub struct UemCommandsAsync<'a> {
+    reader: &'a UemReaderAsync,
+    timeout: Option<Duration>,
+}
+
+impl<'a> UemCommandsAsync<'a> {
+    pub(crate) fn new(reader: &'a UemReaderAsync) -> Self {
+        UemCommandsAsync {reader, timeout: None}
+    }
+
+    /// Limit time of every command of the group and its subgroups
+    ///
+    /// Commands not completed in time fail with
+    /// [`UemError::ReaderTimeout`](crate::errors::UemError::ReaderTimeout).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use uem_reader::reader::{mock::UemReaderMock, asynchronous::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x64], &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    /// # let uem_reader = UemReaderAsync::new(uem_mock.reader());
+    /// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+    /// let version = uem_reader.commands()
+    ///     .with_timeout(Duration::from_millis(100))
+    ///     .reader()
+    ///     .get_version().await;
+    /// # assert!(version.is_ok());
+    /// # });
+    /// # uem_mock.assert_done();
+    /// ```
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Access reader commands group
+    pub fn reader(&self) -> UemCommandsReaderAsync<'a> {
+        UemCommandsReaderAsync {reader: self.reader, timeout: self.timeout}
+    }
+
+    /// Access cards commands group
+    pub fn cards(&self) -> UemCommandsCardsAsync<'a> {
+        UemCommandsCardsAsync {reader: self.reader, timeout: self.timeout}
+    }
+}
+
+/// Asynchronous counterpart of [`UemCommandsReader`](UemCommandsReader)
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{mock::UemReaderMock, asynchronous::*};
+/// # use uem_reader::commands::reader::UemColor;
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x05, 0x01], &[]);
+/// # uem_mock.expect(&[0x07, 0x02, 0x01, 0x00], &[]);
+/// # let uem_reader = UemReaderAsync::new(uem_mock.reader());
+/// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+/// let commands = uem_reader.commands().reader();
+/// assert!(commands.beep(1).await.is_ok());
+/// assert!(commands.led(1, UemColor::Green, UemColor::Off).await.is_ok());
+/// # });
+/// # uem_mock.assert_done();
+/// ```
+pub struct UemCommandsReaderAsync<'a> {
+    reader: &'a UemReaderAsync,
+    timeout: Option<Duration>,
+}
+
+impl<'a> UemCommandsReaderAsync<'a> {
+    /// Limit time of every command of the group
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Beep `count` times,
+    /// see [`beep`](UemCommandsReader::beep)
+    pub async fn beep(&self, count: u8) -> UemResult {
+        let timeout = self.timeout;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).reader().beep(count)
+        }).await
+    }
+
+    /// Blink with led,
+    /// see [`led`](UemCommandsReader::led)
+    pub async fn led(&self, count: u8, color: UemColor, post_color: UemColor) -> UemResult {
+        let timeout = self.timeout;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).reader().led(count, color, post_color)
+        }).await
+    }
+
+    /// Turn radio chip on or off,
+    /// see [`power_radio`](UemCommandsReader::power_radio)
+    pub async fn power_radio(&self, on: bool) -> UemResult {
+        let timeout = self.timeout;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).reader().power_radio(on)
+        }).await
+    }
+
+    /// Turn radio field off for `duration` milliseconds,
+    /// see [`radio_off_on`](UemCommandsReader::radio_off_on)
+    pub async fn radio_off_on(&self, duration: u16) -> UemResult {
+        let timeout = self.timeout;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).reader().radio_off_on(duration)
+        }).await
+    }
+
+    /// Read reader version,
+    /// see [`get_version`](UemCommandsReader::get_version)
+    pub async fn get_version(&self) -> UemResultVec {
+        let timeout = self.timeout;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).reader().get_version()
+        }).await
+    }
+
+    /// Read reader serial,
+    /// see [`get_serial`](UemCommandsReader::get_serial)
+    pub async fn get_serial(&self) -> UemResultVec {
+        let timeout = self.timeout;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).reader().get_serial()
+        }).await
+    }
+}
+
+/// Asynchronous counterpart of [`UemCommandsCards`](UemCommandsCards)
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{mock::UemReaderMock, asynchronous::*};
+/// # use uem_reader::commands::cards::UemActivateParameters;
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x75, 0x00, 0xAA, 0x80], &[0x04, 0x00, 0x08, 0x04, 0x01, 0x02, 0x03, 0x04]);
+/// # let uem_reader = UemReaderAsync::new(uem_mock.reader());
+/// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+/// let card = uem_reader.commands().cards()
+///     .activate_a(&UemActivateParameters::default()).await;
+/// # assert_eq!(card.unwrap().uid, vec![0x01, 0x02, 0x03, 0x04]);
+/// # });
+/// # uem_mock.assert_done();
+/// ```
+pub struct UemCommandsCardsAsync<'a> {
+    reader: &'a UemReaderAsync,
+    timeout: Option<Duration>,
+}
+
+impl<'a> UemCommandsCardsAsync<'a> {
+    /// Limit time of every command of the group
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Access Mifare cards commands group
+    pub fn mifare(&self) -> UemCommandsCardsMifareAsync<'a> {
+        UemCommandsCardsMifareAsync {reader: self.reader, timeout: self.timeout}
+    }
+
+    /// Activation of type ISO14443A card,
+    /// see [`activate_a`](UemCommandsCards::activate_a)
+    pub async fn activate_a(&self, parameters: &UemActivateParameters) -> UemResultCardA {
+        let timeout = self.timeout;
+        let parameters = *parameters;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).cards().activate_a(&parameters)
+        }).await
+    }
+
+    /// Activation of type ISO14443B card,
+    /// see [`activate_b`](UemCommandsCards::activate_b)
+    pub async fn activate_b(&self, parameters: &UemActivateParameters) -> UemResultCardB {
+        let timeout = self.timeout;
+        let parameters = *parameters;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).cards().activate_b(&parameters)
+        }).await
+    }
+}
+
+/// Asynchronous counterpart of [`UemCommandsCardsMifare`](UemCommandsCardsMifare)
+pub struct UemCommandsCardsMifareAsync<'a> {
+    reader: &'a UemReaderAsync,
+    timeout: Option<Duration>,
+}
+
+impl<'a> UemCommandsCardsMifareAsync<'a> {
+    /// Limit time of every command of the group
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Access Mifare Classic cards commands group
+    pub fn classic(&self) -> UemCommandsCardsMifareClassicAsync<'a> {
+        UemCommandsCardsMifareClassicAsync {reader: self.reader, timeout: self.timeout}
+    }
+}
+
+/// Asynchronous counterpart of [`UemCommandsCardsMifareClassic`](UemCommandsCardsMifareClassic)
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{mock::UemReaderMock, asynchronous::*};
+/// # use uem_reader::card::UemCardIso14443A;
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x14, 0x60, 0x01, 0x02, 0x03, 0x04, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x04], &[]);
+/// # uem_mock.expect(&[0x19, 0x05], &[0x00; 16]);
+/// # let uem_reader = UemReaderAsync::new(uem_mock.reader());
+/// # let card = UemCardIso14443A { atq: vec![0x04, 0x00], sak: 0x08, uid: vec![0x01, 0x02, 0x03, 0x04], ats: vec![] };
+/// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+/// let classic = uem_reader.commands().cards().mifare().classic();
+/// if classic.authenticate_key_a(&card, &[0xFF; 6], 1).await.is_ok() {
+///     // Read sector 1, block 1
+///     let data = classic.read(1, 1).await;
+/// #   assert_eq!(data.unwrap().len(), 16);
+/// }
+/// # });
+/// # uem_mock.assert_done();
+/// ```
+pub struct UemCommandsCardsMifareClassicAsync<'a> {
+    reader: &'a UemReaderAsync,
+    timeout: Option<Duration>,
+}
+
+impl<'a> UemCommandsCardsMifareClassicAsync<'a> {
+    /// Limit time of every command of the group
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Authenticate sector with key A,
+    /// see [`authenticate_key_a`](UemCommandsCardsMifareClassic::authenticate_key_a)
+    pub async fn authenticate_key_a(&self, card: &UemCardIso14443A, key: &[u8; 6], sector: u8) -> UemResult {
+        let timeout = self.timeout;
+        let (card, key) = (card.clone(), *key);
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).cards().mifare().classic()
+                .authenticate_key_a(&card, &key, sector)
+        }).await
+    }
+
+    /// Authenticate sector with key B,
+    /// see [`authenticate_key_b`](UemCommandsCardsMifareClassic::authenticate_key_b)
+    pub async fn authenticate_key_b(&self, card: &UemCardIso14443A, key: &[u8; 6], sector: u8) -> UemResult {
+        let timeout = self.timeout;
+        let (card, key) = (card.clone(), *key);
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).cards().mifare().classic()
+                .authenticate_key_b(&card, &key, sector)
+        }).await
+    }
+
+    /// Read card block,
+    /// see [`read`](UemCommandsCardsMifareClassic::read)
+    pub async fn read(&self, sector: u8, block: u8) -> UemResultVec {
+        let timeout = self.timeout;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).cards().mifare().classic()
+                .read(sector, block)
+        }).await
+    }
+
+    /// Write to card block,
+    /// see [`write`](UemCommandsCardsMifareClassic::write)
+    pub async fn write(&self, data: Vec<u8>, sector: u8, block: u8) -> UemResult {
+        let timeout = self.timeout;
+        self.reader.run(timeout, move |reader| {
+            blocking(reader, timeout).cards().mifare().classic()
+                .write(data, sector, block)
+        }).await
+    }
+}
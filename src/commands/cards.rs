@@ -4,67 +4,20 @@
 
 pub mod mifare;
 
+use std::time::Duration;
+
 use crate::reader::*;
+use crate::commands::{LockedReader, ReaderAccess};
 use crate::commands::cards::mifare::*;
-use crate::errors::*;
-use crate::card::*;
-
-#[derive(Debug, Clone, Copy)]
-/// Card activation parameters
-/// 
-/// This structure can be used to both activate cards 
-/// of type ISO14443A and ISO14443B
-pub struct UemActivateParameters {
-    // standard: UemCardStandard,
-    /// Required [baudrate](UemCardBaudrates) of card -> reader channel
-    pub baudrate_card_reader: UemCardBaudrates,
-    /// Required [baudrate](UemCardBaudrates) of reader -> card channel
-    pub baudrate_reader_card: UemCardBaudrates,
-    /// Time in milliseconds to turn radio off before
-    /// requesting next card.
-    pub radio_off_period: u8,
-    /// After a radio field has been turned on,
-    /// it is necessary to give cards little time
-    /// to fully power up. Set in milliseconds.
-    pub pause_after_radio_on: u8,
-    /// Enables cards to be automatically switched to 
-    /// T=CL protocol (ISO14443-4)
-    pub switch_to_tcl: bool,
-    /// If switched to T=CL, then which card identifier
-    /// (CID) to use
-    pub tcl_cid: u8,
-    /// For cards of type ISO14443B - 
-    /// application family identifier
-    pub btype_afi: u8,
-    /// For cards of type ISO14443B - 
-    /// request extended answer to query data
-    pub btype_use_ext_atqb: bool,
-    /// For cards of type ISO14443B - 
-    /// number of time slots to use
-    pub btype_time_slots: u8,
-}
 
-impl Default for UemActivateParameters {
-    fn default() -> UemActivateParameters {
-        UemActivateParameters {
-            // standard: UemCardStandard::Iso14443a,
-            baudrate_card_reader: UemCardBaudrates::Baud106kbps,
-            baudrate_reader_card: UemCardBaudrates::Baud106kbps,
-            radio_off_period: 10,
-            pause_after_radio_on: 10,
-            switch_to_tcl: false,
-            tcl_cid: 0x00,
-            btype_afi: 0x00,
-            btype_use_ext_atqb: false,
-            btype_time_slots: 1,
-        }
-    }
-}
+pub use crate::protocol::command::UemActivateParameters;
+use crate::protocol::command;
 
 /// Structure for commands to interact
 /// with cards
 pub struct UemCommandsCards<'a> {
-    reader: &'a UemReader,
+    reader: ReaderAccess<'a>,
+    timeout: Option<Duration>,
 }
 
 /// Accessing cards related commands group
@@ -74,16 +27,23 @@ pub trait UemCommandsCardsTrait {
 
 impl<'a> UemCommandsCardsMifareTrait for UemCommandsCards<'a> {   
     fn mifare(&mut self) -> UemCommandsCardsMifare {
-        UemCommandsCardsMifare::new(self.as_reader())
+        UemCommandsCardsMifare::new(self.as_reader(), self.timeout)
     }
 }
 
 impl<'a> UemCommandsCards<'a> {
-    pub(crate) fn new(rd: &'a UemReader) -> Self {
-        UemCommandsCards {reader: rd}
+    pub(crate) fn new(rd: ReaderAccess<'a>, timeout: Option<Duration>) -> Self {
+        UemCommandsCards {reader: rd, timeout}
     }
 
-    pub(crate) fn as_reader(&self) -> &'a UemReader {
+    /// Override reader response timeout
+    /// for all commands of the group
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub(crate) fn as_reader(&self) -> ReaderAccess<'a> {
         self.reader
     }
 
@@ -98,46 +58,24 @@ impl<'a> UemCommandsCards<'a> {
     /// `Ok(())` on success, otherwise returns an error.
     /// 
     /// # Example
-    /// ```ignore
+    /// ```
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
+    /// # use uem_reader::commands::{UemCommandsTrait, cards::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x75, 0x00, 0xAA, 0x80], &[0x04, 0x00, 0x08, 0x04, 0x01, 0x02, 0x03, 0x04]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// # if uem_reader.open().is_err() { return; }
     /// let card = uem_reader.commands().cards().activate_a(&UemActivateParameters{
     ///     // switch_to_tcl: true, // Can be used to set T=CL protocol after activation
     ///     ..Default::default()
     /// });
+    /// # assert_eq!(card.unwrap().uid, vec![0x01, 0x02, 0x03, 0x04]);
+    /// # uem_mock.assert_done();
     /// ```
     pub fn activate_a(&mut self, parameters: &UemActivateParameters) -> UemResultCardA {
-        let mut raw_reader = self.reader.lock().unwrap();
-        let mut type_baud: u8 = 0x00;
-        type_baud |= (parameters.baudrate_card_reader as u8) << 2;
-        type_baud |= parameters.baudrate_reader_card as u8;
-        let mut rf_reset: u8 = 0x00;
-        rf_reset |= (parameters.radio_off_period & 0x0F) << 4;
-        rf_reset |= parameters.pause_after_radio_on & 0x0F;
-        let mut disable_tcl_cid: u8 = 0x00;
-        disable_tcl_cid |= (!parameters.switch_to_tcl as u8) << 7;
-        disable_tcl_cid |= parameters.tcl_cid & 0x0F;
-
-        let res = raw_reader.send(&vec![0x75, 
-            type_baud, 
-            rf_reset, 
-            disable_tcl_cid])?;
-        
-        if res.len() < 8 {
-            return Err(UemError::ReaderIncorrectResponse);
-        }
-
-        let atq = res[0..2].to_vec();
-        let sak: u8 = res[2];
-        let uid_len = res[3];
-        let uid = res[4 .. 4 + uid_len as usize].to_vec();
-       
-        if res.len() == 4 + uid_len as usize {
-            return Ok(UemCardIso14443A{atq, sak, uid, ats: vec![]});
-        }
-
-        let ats_len = res[4 + uid_len as usize];
-        let ats = res[4 + uid_len as usize .. 4 + (uid_len + ats_len) as usize].to_vec();
-
-        Ok(UemCardIso14443A{atq, sak, uid, ats})
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        let res = raw_reader.send(&command::activate_a(parameters))?;
+        command::card_a_from_response(&res)
     }
 
     /// Activation of type ISO14443B card
@@ -151,7 +89,14 @@ impl<'a> UemCommandsCards<'a> {
     /// `Ok(())` on success, otherwise returns an error.
     /// 
     /// # Example
-    /// ```ignore
+    /// ```
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
+    /// # use uem_reader::commands::{UemCommandsTrait, cards::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x75, 0x10, 0xAA, 0x00, 0x00, 0x01],
+    /// #     &[0x00, 0x0C, 0x50, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// # if uem_reader.open().is_err() { return; }
     /// let card = uem_reader.commands().cards().activate_b(&UemActivateParameters{
     ///     // switch_to_tcl: true, // Can be used to set T=CL protocol after activation
     ///     ..Default::default()
@@ -161,51 +106,12 @@ impl<'a> UemCommandsCards<'a> {
     ///     return;
     /// }
     /// let card = card.unwrap();
+    /// # assert_eq!(card.pupi, vec![0x01, 0x02, 0x03, 0x04]);
+    /// # uem_mock.assert_done();
     /// ```
     pub fn activate_b(&mut self, parameters: &UemActivateParameters) -> UemResultCardB {
-        let mut raw_reader = self.reader.lock().unwrap();
-        let mut type_baud: u8 = 0b_0001_0000;
-        type_baud |= (parameters.baudrate_card_reader as u8) << 2;
-        type_baud |= parameters.baudrate_reader_card as u8;
-        let mut rf_reset: u8 = 0x00;
-        rf_reset |= (parameters.radio_off_period & 0x0F) << 4;
-        rf_reset |= parameters.pause_after_radio_on & 0x0F;
-        let mut disable_tcl_cid: u8 = 0x00;
-        disable_tcl_cid |= (parameters.switch_to_tcl as u8) << 7;
-        disable_tcl_cid |= parameters.tcl_cid & 0x0F;
-        let mut param: u8 = 0x00;
-        param |= (parameters.btype_use_ext_atqb as u8) << 4;
-        param |= parameters.btype_time_slots & 0x07;
-
-        let res = raw_reader.send(&vec![0x75, 
-            type_baud, 
-            rf_reset, 
-            disable_tcl_cid, 
-            parameters.btype_afi, 
-            param])?;
-
-        if res.len() < 2 {
-            return Err(UemError::ReaderIncorrectResponse);
-        }
-
-        let mbli = res[0];
-        let atqb_len = res[1];
-
-        if atqb_len < 12 {
-            return Err(UemError::ReaderIncorrectResponse);
-        }
-
-        let atqb = res[2 .. 2 + atqb_len as usize].to_vec();
-        let pupi = res[3..7].to_vec();
-        let app_data = res[7..11].to_vec();
-        let prot_info = res[11..14].to_vec();
-        
-        Ok(UemCardIso14443B{
-            mbli, 
-            pupi,
-            app_data,
-            prot_info,
-            atq: atqb,
-        })
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        let res = raw_reader.send(&command::activate_b(parameters))?;
+        command::card_b_from_response(&res)
     }
 }
@@ -2,13 +2,16 @@
 
 pub mod classic;
 
-use crate::reader::*;
+use std::time::Duration;
+
+use crate::commands::ReaderAccess;
 use crate::commands::cards::mifare::classic::*;
 
 /// Structure for commands to interact
 /// with Mifare cards
 pub struct UemCommandsCardsMifare<'a> {
-    reader: &'a UemReader,
+    reader: ReaderAccess<'a>,
+    timeout: Option<Duration>,
 }
 
 /// Accessing Mifare cards related commands group
@@ -18,16 +21,23 @@ pub trait UemCommandsCardsMifareTrait {
 
 impl<'a> UemCommandsCardsMifareClassicTrait for UemCommandsCardsMifare<'a> {   
     fn classic(&mut self) -> UemCommandsCardsMifareClassic {
-        UemCommandsCardsMifareClassic::new(self.as_reader())
+        UemCommandsCardsMifareClassic::new(self.as_reader(), self.timeout)
     }
 }
 
 impl<'a> UemCommandsCardsMifare<'a> {
-    pub(crate) fn new(rd: &'a UemReader) -> Self {
-        UemCommandsCardsMifare {reader: rd}
+    pub(crate) fn new(rd: ReaderAccess<'a>, timeout: Option<Duration>) -> Self {
+        UemCommandsCardsMifare {reader: rd, timeout}
+    }
+
+    /// Override reader response timeout
+    /// for all commands of the group
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
     }
 
-    pub(crate) fn as_reader(&self) -> &'a UemReader {
+    pub(crate) fn as_reader(&self) -> ReaderAccess<'a> {
         self.reader
     }
 }
@@ -1,11 +1,14 @@
 //! Grouping of commands related to Mifare Classic cards type
 
-use crate::{reader::*, card::UemCardIso14443A, helpers::get_absolute_block_address, errors::UemError};
+use std::time::Duration;
+
+use crate::{reader::*, commands::{LockedReader, ReaderAccess}, card::UemCardIso14443A, errors::UemError, protocol::command};
 
 /// Structure for commands to interact
 /// with Mifare Classic cards
 pub struct UemCommandsCardsMifareClassic<'a> {
-    reader: &'a UemReader,
+    reader: ReaderAccess<'a>,
+    timeout: Option<Duration>,
 }
 
 /// Accessing Mifare Classic cards related commands group
@@ -14,8 +17,15 @@ pub trait UemCommandsCardsMifareClassicTrait {
 }
 
 impl<'a> UemCommandsCardsMifareClassic<'a> {
-    pub(crate) fn new(rd: &'a UemReader) -> Self {
-        UemCommandsCardsMifareClassic {reader: rd}
+    pub(crate) fn new(rd: ReaderAccess<'a>, timeout: Option<Duration>) -> Self {
+        UemCommandsCardsMifareClassic {reader: rd, timeout}
+    }
+
+    /// Override reader response timeout
+    /// for all commands of the group
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
     }
 
     /// Authenticate Mifare Classic card with key A
@@ -33,29 +43,31 @@ impl<'a> UemCommandsCardsMifareClassic<'a> {
     /// 
     /// # Example
     /// 
-    /// ```ignore
+    /// ```
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
+    /// # use uem_reader::commands::{UemCommandsTrait, cards::{UemCommandsCardsTrait, mifare::{UemCommandsCardsMifareTrait, classic::*}}};
+    /// # use uem_reader::card::UemCardIso14443A;
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x14, 0x60, 0x01, 0x02, 0x03, 0x04, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x04], &[]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// # if uem_reader.open().is_err() { return; }
+    /// # let card = UemCardIso14443A { atq: vec![0x04, 0x00], sak: 0x08, uid: vec![0x01, 0x02, 0x03, 0x04], ats: vec![] };
     /// // Authenticate sector 1 with FF... key
     /// let res = uem_reader.commands().cards().mifare().classic()
     ///     .authenticate_key_a(
     ///         &card, 
-    ///         &vec![0xFF; 6], 
+    ///         &[0xFF; 6], 
     ///         1
     ///     );
     /// if res.is_err() {
     ///     uem_reader.close();
     ///     return;
     /// }
+    /// # uem_mock.assert_done();
     /// ```
     pub fn authenticate_key_a(&mut self, card: &UemCardIso14443A, key: &[u8; 6], sector: u8) -> UemResult {
-        let mut raw_reader = self.reader.lock().unwrap();
-        let command :Vec<u8> = vec![0x14, 0x60].iter().cloned().chain(
-            card.uid.iter().rev().take(4).rev().cloned().chain(
-                key.iter().cloned().chain(
-                    vec![get_absolute_block_address(sector, 0)].iter().cloned()
-                )
-            )
-        ).collect();
-        raw_reader.send(&command).map(|_| ())
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send(&command::classic_authenticate_key_a(&card.uid, key, sector)).map(|_| ())
     }
 
     /// Authenticate Mifare Classic card with key B
@@ -73,29 +85,31 @@ impl<'a> UemCommandsCardsMifareClassic<'a> {
     /// 
     /// # Example
     /// 
-    /// ```ignore
+    /// ```
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
+    /// # use uem_reader::commands::{UemCommandsTrait, cards::{UemCommandsCardsTrait, mifare::{UemCommandsCardsMifareTrait, classic::*}}};
+    /// # use uem_reader::card::UemCardIso14443A;
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x14, 0x61, 0x01, 0x02, 0x03, 0x04, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x04], &[]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// # if uem_reader.open().is_err() { return; }
+    /// # let card = UemCardIso14443A { atq: vec![0x04, 0x00], sak: 0x08, uid: vec![0x01, 0x02, 0x03, 0x04], ats: vec![] };
     /// // Authenticate sector 1 with FF... key
     /// let res = uem_reader.commands().cards().mifare().classic()
     ///     .authenticate_key_b(
     ///         &card, 
-    ///         &vec![0xFF; 6], 
+    ///         &[0xFF; 6], 
     ///         1
     ///     );
     /// if res.is_err() {
     ///     uem_reader.close();
     ///     return;
     /// }
+    /// # uem_mock.assert_done();
     /// ```
     pub fn authenticate_key_b(&mut self, card: &UemCardIso14443A, key: &[u8; 6], sector: u8) -> UemResult {
-        let mut raw_reader = self.reader.lock().unwrap();
-        let command: Vec<u8> = vec![0x14, 0x61].iter().cloned().chain(
-            card.uid.iter().rev().take(4).rev().cloned().chain(
-                key.iter().cloned().chain(
-                    vec![get_absolute_block_address(sector, 0)].iter().cloned()
-                )
-            )
-        ).collect();
-        raw_reader.send(&command).map(|_| ())
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send(&command::classic_authenticate_key_b(&card.uid, key, sector)).map(|_| ())
     }
 
     /// Read specific Mifare Classic card block
@@ -112,7 +126,14 @@ impl<'a> UemCommandsCardsMifareClassic<'a> {
     /// 
     /// # Example
     /// 
-    /// ```ignore
+    /// ```
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
+    /// # use uem_reader::commands::{UemCommandsTrait, cards::{UemCommandsCardsTrait, mifare::{UemCommandsCardsMifareTrait, classic::*}}};
+    /// # use uem_reader::card::UemCardIso14443A;
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x19, 0x05], &[0x00; 16]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// # if uem_reader.open().is_err() { return; }
     /// // Read sector 1, block 1
     /// let res = uem_reader.commands().cards().mifare().classic()
     ///     .read(1, 1);
@@ -121,16 +142,61 @@ impl<'a> UemCommandsCardsMifareClassic<'a> {
     ///     return;
     /// }
     /// let data = res.unwrap();
+    /// # uem_mock.assert_done();
     /// ```
     pub fn read(&mut self, sector: u8, block: u8) -> UemResultVec {
-        let mut raw_reader = self.reader.lock().unwrap();
-        let res = raw_reader.send(&vec![0x19, get_absolute_block_address(sector, block)])?;
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        let res = raw_reader.send(&command::classic_read(sector, block))?;
         if res.len() != 16 {
             return Err(UemError::ReaderIncorrectResponse);
         }
         Ok(res)
     }
 
+    /// Read specific Mifare Classic card block
+    /// into a caller-supplied buffer
+    /// 
+    /// Unlike [`read`](UemCommandsCardsMifareClassic::read)
+    /// the command is sent without memory allocations.
+    /// 
+    /// # Arguments
+    ///
+    /// * `sector` - A sector number (0-based) to use
+    /// * `block` - A block number (0-based) within the sector to read
+    /// * `data` - A buffer to store 16-byte block data
+    /// 
+    /// # Returns
+    /// 
+    /// `Ok(())` on success, otherwise returns an error.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// # use uem_reader::reader::mock::UemReaderMock;
+    /// # use uem_reader::commands::{UemCommandsTrait, cards::{UemCommandsCardsTrait, mifare::{UemCommandsCardsMifareTrait, classic::*}}};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x19, 0x05], &[0x00; 16]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// let mut data = [0u8; 16];
+    /// // Read sector 1, block 1
+    /// let res = uem_reader.commands().cards().mifare().classic()
+    ///     .read_into(1, 1, &mut data);
+    /// assert!(res.is_ok());
+    /// # uem_mock.assert_done();
+    /// ```
+    pub fn read_into(&mut self, sector: u8, block: u8, data: &mut [u8; 16]) -> UemResult {
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        let length = raw_reader.send_into(&command::classic_read(sector, block), data)
+            .map_err(|e| match e {
+                UemError::IncorrectParameter => UemError::ReaderIncorrectResponse,
+                e => e,
+            })?;
+        if length != 16 {
+            return Err(UemError::ReaderIncorrectResponse);
+        }
+        Ok(())
+    }
+
     /// Write to specific Mifare Classic card block
     /// 
     /// # Arguments
@@ -145,22 +211,28 @@ impl<'a> UemCommandsCardsMifareClassic<'a> {
     /// 
     /// # Example
     /// 
-    /// ```ignore
+    /// ```
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
+    /// # use uem_reader::commands::{UemCommandsTrait, cards::{UemCommandsCardsTrait, mifare::{UemCommandsCardsMifareTrait, classic::*}}};
+    /// # use uem_reader::card::UemCardIso14443A;
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x1A, 0x05, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+    /// #     0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F], &[]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// # if uem_reader.open().is_err() { return; }
+    /// # let data = (0x00..0x10).collect::<Vec<u8>>();
     /// // Write data to sector 1, block 1
     /// let res = uem_reader.commands().cards().mifare().classic()
-    ///     .write(1, 1, &data);
+    ///     .write(data, 1, 1);
     /// if res.is_err() {
     ///     uem_reader.close();
     ///     return;
     /// }
+    /// # uem_mock.assert_done();
     /// ```
     pub fn write(&mut self, data: Vec<u8>, sector: u8, block: u8) -> UemResult {
-        if data.len() != 16 {
-            return Err(UemError::IncorrectParameter);
-        }
-        let mut raw_reader = self.reader.lock().unwrap();
-        let command: Vec<u8> = vec![0x1A, 
-        get_absolute_block_address(sector, block)].iter().cloned().chain(data).collect();
+        let command = command::classic_write(&data, sector, block)?;
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
         raw_reader.send(&command).map(|_| ())
     }
 }
\ No newline at end of file
@@ -3,30 +3,19 @@
 
 #![allow(dead_code)]
 
-use crate::reader::*;
-use crate::errors::*;
+use std::time::Duration;
 
-use enum_iterator::Sequence;
+use crate::reader::*;
+use crate::commands::{LockedReader, ReaderAccess};
 
-#[repr(u8)]
-#[derive(Debug, PartialEq, Sequence, Clone, Copy)]
-/// LED color combinations
-pub enum UemColor {
-    /// LED is off
-    Off = 0b000,
-    Red = 0b001,
-    Green = 0b010,
-    Blue = 0b100,
-    Yellow = 0b011,
-    Magenta = 0b101,
-    Cyan = 0b110,
-    White = 0b111,
-}
+pub use crate::protocol::command::UemColor;
+use crate::protocol::command;
 
 /// Structure for commands controlling 
 /// a reader itself
 pub struct UemCommandsReader<'a> {
-    reader: &'a UemReader,
+    reader: ReaderAccess<'a>,
+    timeout: Option<Duration>,
 }
 
 /// Accessing reader related commands group
@@ -35,8 +24,15 @@ pub trait UemCommandsReaderTrait {
 }
 
 impl<'a> UemCommandsReader<'a> {
-    pub(crate) fn new(rd: &'a UemReader) -> Self {
-        UemCommandsReader {reader: rd}
+    pub(crate) fn new(rd: ReaderAccess<'a>, timeout: Option<Duration>) -> Self {
+        UemCommandsReader {reader: rd, timeout}
+    }
+
+    /// Override reader response timeout
+    /// for all commands of the group
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
     }
 
     /// Make short sound signals of specific count
@@ -52,13 +48,11 @@ impl<'a> UemCommandsReader<'a> {
     /// # Example
     /// 
     /// ```
-    /// # use uem_reader::reader::{UemReaderInternalTrait, usb::find_usb_readers};
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
     /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
-    /// # let mut uem_readers = find_usb_readers();
-    /// # if uem_readers.is_empty() { return; }
-    /// # let uem_reader = uem_readers.get_mut(0);
-    /// # if uem_reader.is_none() { return; }
-    /// # let uem_reader = uem_reader.unwrap();
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x05, 0x05], &[]);
+    /// # let uem_reader = &mut uem_mock.reader();
     /// # if uem_reader.open().is_err() { return; }
     /// // Beep 5 times
     /// if uem_reader.commands().reader()
@@ -67,13 +61,12 @@ impl<'a> UemCommandsReader<'a> {
     ///     return;
     /// }
     /// # if uem_reader.close().is_err() { return; }
+    /// # uem_mock.assert_done();
     /// ```
     pub fn beep(&mut self, count: u8) -> UemResult {
-        if count < 1 {
-            return Err(UemError::IncorrectParameter);
-        }
-        let mut raw_reader = self.reader.lock().unwrap();
-        raw_reader.send(&vec![0x05, count]).map(|_| ())
+        let command = command::beep(count)?;
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send(&command).map(|_| ())
     }
 
     /// Blink `count` times with led of specific color
@@ -92,15 +85,11 @@ impl<'a> UemCommandsReader<'a> {
     /// # Example
     /// 
     /// ```
-    /// # use uem_reader::reader::{
-    ///     UemReaderInternalTrait,
-    ///     usb::find_usb_readers};
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
     /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
-    /// # let mut uem_readers = find_usb_readers();
-    /// # if uem_readers.is_empty() { return; }
-    /// # let uem_reader = uem_readers.get_mut(0);
-    /// # if uem_reader.is_none() { return; }
-    /// # let uem_reader = uem_reader.unwrap();
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x07, 0x02, 0x03, 0x03], &[]);
+    /// # let uem_reader = &mut uem_mock.reader();
     /// # if uem_reader.open().is_err() { return; }
     /// // Blink 3 times with green and remain yellow
     /// if uem_reader.commands().reader()
@@ -109,10 +98,11 @@ impl<'a> UemCommandsReader<'a> {
     ///     return;
     /// }
     /// # if uem_reader.close().is_err() { return; }
+    /// # uem_mock.assert_done();
     /// ```
     pub fn led(&mut self, count: u8, color: UemColor, post_color: UemColor) -> UemResult {
-        let mut raw_reader = self.reader.lock().unwrap();
-        raw_reader.send(&vec![0x07, color as u8, count, post_color as u8]).map(|_| ())
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send(&command::led(count, color, post_color)).map(|_| ())
     }
 
     /// Turn radio chip on
@@ -124,18 +114,16 @@ impl<'a> UemCommandsReader<'a> {
     /// 
     /// # Returns
     /// 
-    /// `Ok(())` on success, otherwise returns an [`UemError`](UemError).
+    /// `Ok(())` on success, otherwise returns an [`UemError`](crate::errors::UemError).
     /// 
     /// # Example
     /// 
     /// ```
-    /// # use uem_reader::reader::{UemReaderInternalTrait, usb::find_usb_readers};
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
     /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
-    /// # let mut uem_readers = find_usb_readers();
-    /// # if uem_readers.is_empty() { return; }
-    /// # let uem_reader = uem_readers.get_mut(0);
-    /// # if uem_reader.is_none() { return; }
-    /// # let uem_reader = uem_reader.unwrap();
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x10], &[]);
+    /// # let uem_reader = &mut uem_mock.reader();
     /// # if uem_reader.open().is_err() { return; }
     /// let mut uem_cmds = uem_reader.commands();
     /// let mut uem_cmds_reader = uem_cmds.reader();
@@ -143,13 +131,11 @@ impl<'a> UemCommandsReader<'a> {
     ///     return;
     /// }
     /// # if uem_reader.close().is_err() { return; }
+    /// # uem_mock.assert_done();
     /// ```
     pub fn power_radio(&mut self, on: bool) -> UemResult {
-        let mut raw_reader = self.reader.lock().unwrap();
-        match on {
-            true => raw_reader.send(&vec![0x10]).map(|_| ()),
-            false => raw_reader.send(&vec![0x04, 0x80, 0x01]).map(|_| ())
-        }
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send(&command::power_radio(on)).map(|_| ())
     }
 
     /// Switch radio field off for a specified duration
@@ -172,13 +158,11 @@ impl<'a> UemCommandsReader<'a> {
     /// # Example
     /// 
     /// ```
-    /// # use uem_reader::reader::{UemReaderInternalTrait, usb::find_usb_readers};
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
     /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
-    /// # let mut uem_readers = find_usb_readers();
-    /// # if uem_readers.is_empty() { return; }
-    /// # let uem_reader = uem_readers.get_mut(0);
-    /// # if uem_reader.is_none() { return; }
-    /// # let uem_reader = uem_reader.unwrap();
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x05, 0x0A, 0x00], &[]);
+    /// # let uem_reader = &mut uem_mock.reader();
     /// # if uem_reader.open().is_err() { return; }
     /// // Switch off radio for 10 ms
     /// if uem_reader.commands().reader()
@@ -187,13 +171,11 @@ impl<'a> UemCommandsReader<'a> {
     ///     return;
     /// }
     /// # if uem_reader.close().is_err() { return; }
+    /// # uem_mock.assert_done();
     /// ```
     pub fn radio_off_on(&mut self, duration: u16) -> UemResult {
-        let mut raw_reader = self.reader.lock().unwrap();
-        raw_reader.send(&vec![0x05, 
-            (duration & 0x00FF) as u8,
-            ((duration & 0xFF00) >> 8) as u8]
-        ).map(|_| ())
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send(&command::radio_off_on(duration)).map(|_| ())
     }
 
     /// Read reader version
@@ -203,18 +185,16 @@ impl<'a> UemCommandsReader<'a> {
     /// # Returns
     /// 
     /// `Ok(Vec<u8>)` containing the version,
-    /// otherwise [`UemError`](UemError).
+    /// otherwise [`UemError`](crate::errors::UemError).
     /// 
     /// # Example
     /// 
     /// ```
-    /// # use uem_reader::reader::{UemReaderInternalTrait, usb::find_usb_readers};
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
     /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
-    /// # let mut uem_readers = find_usb_readers();
-    /// # if uem_readers.is_empty() { return; }
-    /// # let uem_reader = uem_readers.get_mut(0);
-    /// # if uem_reader.is_none() { return; }
-    /// # let uem_reader = uem_reader.unwrap();
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x64], &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    /// # let uem_reader = &mut uem_mock.reader();
     /// # if uem_reader.open().is_err() { return; }
     /// match uem_reader.commands().reader()
     /// .get_version() {
@@ -226,10 +206,11 @@ impl<'a> UemCommandsReader<'a> {
     ///     }
     /// }
     /// # if uem_reader.close().is_err() { return; }
+    /// # uem_mock.assert_done();
     /// ```
     pub fn get_version(&mut self) -> UemResultVec {
-        let mut raw_reader = self.reader.lock().unwrap();
-        raw_reader.send(&vec![0x64])
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send(&command::get_version())
     }
 
     /// Read reader serial
@@ -239,18 +220,16 @@ impl<'a> UemCommandsReader<'a> {
     /// # Returns
     /// 
     /// `Ok(Vec<u8>)` containing the serial,
-    /// otherwise [`UemError`](UemError).
+    /// otherwise [`UemError`](crate::errors::UemError).
     /// 
     /// # Example
     /// 
     /// ```
-    /// # use uem_reader::reader::{UemReaderInternalTrait, usb::find_usb_readers};
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
     /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
-    /// # let mut uem_readers = find_usb_readers();
-    /// # if uem_readers.is_empty() { return; }
-    /// # let uem_reader = uem_readers.get_mut(0);
-    /// # if uem_reader.is_none() { return; }
-    /// # let uem_reader = uem_reader.unwrap();
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+    /// # let uem_reader = &mut uem_mock.reader();
     /// # if uem_reader.open().is_err() { return; }
     /// match uem_reader.commands().reader()
     /// .get_serial() {
@@ -262,9 +241,76 @@ impl<'a> UemCommandsReader<'a> {
     ///     }
     /// }
     /// # if uem_reader.close().is_err() { return; }
+    /// # uem_mock.assert_done();
     /// ```
     pub fn get_serial(&mut self) -> UemResultVec {
-        let mut raw_reader = self.reader.lock().unwrap();
-        raw_reader.send(&vec![0x22])
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send(&command::get_serial())
+    }
+
+    /// Read reader version into a caller-supplied buffer
+    /// 
+    /// Unlike [`get_version`](UemCommandsReader::get_version)
+    /// the command is sent without memory allocations.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `version` - A buffer of at least 6 bytes to store the version
+    /// 
+    /// # Returns
+    /// 
+    /// `Ok(usize)` containing the version length,
+    /// otherwise [`UemError`](crate::errors::UemError).
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// # use uem_reader::reader::mock::UemReaderMock;
+    /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x64], &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// let mut version = [0u8; 6];
+    /// let length = uem_reader.commands().reader()
+    ///     .get_version_into(&mut version);
+    /// assert_eq!(length.unwrap(), 6);
+    /// # uem_mock.assert_done();
+    /// ```
+    pub fn get_version_into(&mut self, version: &mut [u8]) -> UemResultLen {
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send_into(&command::get_version(), version)
+    }
+
+    /// Read reader serial into a caller-supplied buffer
+    /// 
+    /// Unlike [`get_serial`](UemCommandsReader::get_serial)
+    /// the command is sent without memory allocations.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `serial` - A buffer of at least 4 bytes to store the serial
+    /// 
+    /// # Returns
+    /// 
+    /// `Ok(usize)` containing the serial length,
+    /// otherwise [`UemError`](crate::errors::UemError).
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// # use uem_reader::reader::mock::UemReaderMock;
+    /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+    /// # let uem_reader = &mut uem_mock.reader();
+    /// let mut serial = [0u8; 4];
+    /// let length = uem_reader.commands().reader()
+    ///     .get_serial_into(&mut serial);
+    /// assert_eq!(length.unwrap(), 4);
+    /// # uem_mock.assert_done();
+    /// ```
+    pub fn get_serial_into(&mut self, serial: &mut [u8]) -> UemResultLen {
+        let mut raw_reader = LockedReader::new(self.reader, self.timeout)?;
+        raw_reader.send_into(&command::get_serial(), serial)
     }
 }
@@ -0,0 +1,152 @@
+//! Exclusive multi-command sessions
+//!
+//! Every command of a [group](crate::commands) locks its reader
+//! on its own, so commands of other threads may run in between.
+//! A [session](UemSession) holds the reader lock until it is
+//! dropped, so command sequences depending on card state,
+//! e.g. activation, authentication and block reading,
+//! are not interrupted.
+//!
+//! Inside a session the reader object itself must not be used
+//! from the same thread, it would wait for the session forever.
+
+use std::cell::{RefCell, RefMut};
+use std::time::Duration;
+
+use crate::reader::*;
+use crate::errors::UemError;
+use crate::commands::*;
+
+/// Reader held by a session, borrowed by command groups
+/// for a single command
+pub(crate) trait SessionReader {
+    fn borrow_reader(&self) -> core::result::Result<RefMut<'_, dyn UemReaderInternalTrait + Send + 'static>, UemError>;
+}
+
+impl SessionReader for RefCell<ReaderGuard<'_>> {
+    fn borrow_reader(&self) -> core::result::Result<RefMut<'_, dyn UemReaderInternalTrait + Send + 'static>, UemError> {
+        let guard = self.try_borrow_mut().map_err(|_| UemError::PendingOperation)?;
+        Ok(RefMut::map(guard, |reader| &mut **reader))
+    }
+}
+
+/// Reader held for exclusive use
+///
+/// The session provides the same [command groups](UemCommands)
+/// as a reader object and releases the reader when dropped.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::mock::UemReaderMock;
+/// # use uem_reader::commands::{UemCommandsTrait, session::*};
+/// # use uem_reader::commands::cards::{*, mifare::{UemCommandsCardsMifareTrait, classic::*}};
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x75, 0x00, 0xAA, 0x80], &[0x04, 0x00, 0x08, 0x04, 0x01, 0x02, 0x03, 0x04]);
+/// # uem_mock.expect(&[0x14, 0x60, 0x01, 0x02, 0x03, 0x04, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x04], &[]);
+/// # uem_mock.expect(&[0x19, 0x05], &[0x00; 16]);
+/// # let uem_reader = uem_mock.reader();
+/// let mut session = uem_reader.session().unwrap();
+///
+/// let card = session.commands().cards().activate_a(&UemActivateParameters::default()).unwrap();
+/// session.commands().cards().mifare().classic()
+///     .authenticate_key_a(&card, &[0xFF; 6], 1).unwrap();
+/// let data = session.commands().cards().mifare().classic()
+///     .read(1, 1).unwrap();
+/// # assert_eq!(data.len(), 16);
+///
+/// // Other threads may use the reader again
+/// drop(session);
+/// # uem_mock.assert_done();
+/// ```
+pub struct UemSession<'r> {
+    reader: RefCell<ReaderGuard<'r>>,
+}
+
+impl<'r> UemSession<'r> {
+    pub(crate) fn new(reader: &'r UemReader) -> core::result::Result<Self, UemError> {
+        Ok(UemSession { reader: RefCell::new(lock_reader(reader)?) })
+    }
+
+    /// Send a raw command within the session
+    pub fn send(&mut self, command: &[u8]) -> UemResultVec {
+        self.reader.get_mut().send(command)
+    }
+
+    /// Send a raw command within the session and write
+    /// response into a caller-supplied buffer
+    pub fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        self.reader.get_mut().send_into(command, response)
+    }
+
+    /// Send a raw command within the session
+    /// with a specific response timeout
+    pub fn send_with_timeout(&mut self, command: &[u8], timeout: Duration) -> UemResultVec {
+        self.reader.get_mut().send_with_timeout(command, timeout)
+    }
+
+    /// Default response timeout of the held reader
+    pub(crate) fn timeout(&self) -> Duration {
+        self.reader.borrow().timeout()
+    }
+
+    /// Set default response timeout of the held reader
+    pub(crate) fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        self.reader.get_mut().set_timeout(timeout)
+    }
+}
+
+impl UemCommandsTrait for UemSession<'_> {
+    fn commands(&mut self) -> UemCommands<'_> {
+        UemCommands::in_session(&self.reader)
+    }
+}
+
+/// Opening exclusive sessions with a reader
+pub trait UemSessionTrait {
+    /// Hold the reader until the returned
+    /// [session](UemSession) is dropped
+    ///
+    /// # Returns
+    ///
+    /// `Ok(UemSession)` once other threads have released the reader,
+    /// [`UemError::ReaderPoisoned`](UemError::ReaderPoisoned)
+    /// if a thread panicked while using it.
+    fn session(&self) -> core::result::Result<UemSession<'_>, UemError>;
+
+    /// Run an `operation` holding the reader exclusively
+    ///
+    /// # Returns
+    ///
+    /// Result of the `operation`, or an error if the reader
+    /// cannot be held, see [`session`](UemSessionTrait::session).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use uem_reader::reader::mock::UemReaderMock;
+    /// # use uem_reader::commands::{UemCommandsTrait, session::*};
+    /// # use uem_reader::commands::{reader::*, cards::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x75, 0x00, 0xAA, 0x80], &[0x04, 0x00, 0x08, 0x04, 0x01, 0x02, 0x03, 0x04]);
+    /// # uem_mock.expect(&[0x05, 0x01], &[]);
+    /// # let uem_reader = uem_mock.reader();
+    /// // Beep only if the card is still in the field
+    /// let card = uem_reader.with_session(|session| {
+    ///     let card = session.commands().cards().activate_a(&UemActivateParameters::default())?;
+    ///     session.commands().reader().beep(1)?;
+    ///     Ok(card)
+    /// });
+    /// # assert!(card.is_ok());
+    /// # uem_mock.assert_done();
+    /// ```
+    fn with_session<T>(&self, operation: impl FnOnce(&mut UemSession<'_>) -> core::result::Result<T, UemError>) -> core::result::Result<T, UemError> {
+        operation(&mut self.session()?)
+    }
+}
+
+impl UemSessionTrait for UemReader {
+    fn session(&self) -> core::result::Result<UemSession<'_>, UemError> {
+        UemSession::new(self)
+    }
+}
@@ -0,0 +1,342 @@
+//! Command groups of reader workers
+//!
+//! Groups mirror blocking [reader](crate::commands::reader),
+//! [cards](crate::commands::cards) and
+//! [Mifare Classic](crate::commands::cards::mifare::classic) groups
+//! and are accessed through [`UemReaderWorker::commands`](UemReaderWorker::commands).
+//! Every command is queued as a separate job with
+//! [parameters](UemJobParameters) of its group and
+//! returns a [pending result](UemPending).
+
+use std::time::{Duration, Instant};
+
+use crate::reader::worker::*;
+use crate::commands::{*, reader::*, cards::{*, mifare::{*, classic::*}}, session::*};
+use crate::card::*;
+
+/// Structure for grouping worker commands in general
+pub struct UemCommandsWorker<'a> {
+    worker: &'a UemReaderWorker,
+    parameters: UemJobParameters,
+}
+
+impl<'a> UemCommandsWorker<'a> {
+    pub(crate) fn new(worker: &'a UemReaderWorker) -> Self {
+        UemCommandsWorker {worker, parameters: UemJobParameters::default()}
+    }
+
+    /// Queue commands of the group and its subgroups
+    /// with a `priority`
+    pub fn with_priority(mut self, priority: UemPriority) -> Self {
+        self.parameters.priority = priority;
+        self
+    }
+
+    /// Limit every command of the group and its subgroups
+    /// by a `deadline`, see [`UemJobParameters`](UemJobParameters)
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.parameters.deadline = Some(deadline);
+        self
+    }
+
+    /// Limit every command of the group and its subgroups
+    /// by a deadline `timeout` from now
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use uem_reader::reader::{mock::UemReaderMock, worker::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x64], &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    /// # let uem_worker = UemReaderWorker::new(uem_mock.reader());
+    /// let version = uem_worker.commands()
+    ///     .with_timeout(Duration::from_millis(100))
+    ///     .reader()
+    ///     .get_version();
+    /// # assert!(version.wait().is_ok());
+    /// # drop(uem_worker);
+    /// # uem_mock.assert_done();
+    /// ```
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    /// Access reader commands group
+    pub fn reader(&self) -> UemCommandsReaderWorker<'a> {
+        UemCommandsReaderWorker {worker: self.worker, parameters: self.parameters}
+    }
+
+    /// Access cards commands group
+    pub fn cards(&self) -> UemCommandsCardsWorker<'a> {
+        UemCommandsCardsWorker {worker: self.worker, parameters: self.parameters}
+    }
+
+    /// Queue a raw command
+    pub fn send(&self, command: &[u8]) -> UemPending<Vec<u8>> {
+        let command = command.to_vec();
+        self.worker.submit(&self.parameters, move |session| session.send(&command))
+    }
+
+    /// Queue a sequence of commands run as a single job,
+    /// see [`submit`](UemReaderWorker::submit)
+    pub fn run<T, F>(&self, job: F) -> UemPending<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut UemSession<'_>) -> core::result::Result<T, crate::errors::UemError> + Send + 'static,
+    {
+        self.worker.submit(&self.parameters, job)
+    }
+}
+
+/// Worker counterpart of [`UemCommandsReader`](UemCommandsReader)
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{mock::UemReaderMock, worker::*};
+/// # use uem_reader::commands::reader::UemColor;
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x05, 0x01], &[]);
+/// # uem_mock.expect(&[0x07, 0x02, 0x01, 0x00], &[]);
+/// # let uem_worker = UemReaderWorker::new(uem_mock.reader());
+/// let commands = uem_worker.commands().reader();
+/// let beep = commands.beep(1);
+/// let led = commands.led(1, UemColor::Green, UemColor::Off);
+/// assert!(beep.wait().is_ok());
+/// assert!(led.wait().is_ok());
+/// # drop(uem_worker);
+/// # uem_mock.assert_done();
+/// ```
+pub struct UemCommandsReaderWorker<'a> {
+    worker: &'a UemReaderWorker,
+    parameters: UemJobParameters,
+}
+
+impl<'a> UemCommandsReaderWorker<'a> {
+    /// Queue commands of the group with a `priority`
+    pub fn with_priority(mut self, priority: UemPriority) -> Self {
+        self.parameters.priority = priority;
+        self
+    }
+
+    /// Limit every command of the group by a `deadline`
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.parameters.deadline = Some(deadline);
+        self
+    }
+
+    /// Beep `count` times,
+    /// see [`beep`](UemCommandsReader::beep)
+    pub fn beep(&self, count: u8) -> UemPending<()> {
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().reader().beep(count)
+        })
+    }
+
+    /// Blink with led,
+    /// see [`led`](UemCommandsReader::led)
+    pub fn led(&self, count: u8, color: UemColor, post_color: UemColor) -> UemPending<()> {
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().reader().led(count, color, post_color)
+        })
+    }
+
+    /// Turn radio chip on or off,
+    /// see [`power_radio`](UemCommandsReader::power_radio)
+    pub fn power_radio(&self, on: bool) -> UemPending<()> {
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().reader().power_radio(on)
+        })
+    }
+
+    /// Turn radio field off for `duration` milliseconds,
+    /// see [`radio_off_on`](UemCommandsReader::radio_off_on)
+    pub fn radio_off_on(&self, duration: u16) -> UemPending<()> {
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().reader().radio_off_on(duration)
+        })
+    }
+
+    /// Read reader version,
+    /// see [`get_version`](UemCommandsReader::get_version)
+    pub fn get_version(&self) -> UemPending<Vec<u8>> {
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().reader().get_version()
+        })
+    }
+
+    /// Read reader serial,
+    /// see [`get_serial`](UemCommandsReader::get_serial)
+    pub fn get_serial(&self) -> UemPending<Vec<u8>> {
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().reader().get_serial()
+        })
+    }
+}
+
+/// Worker counterpart of [`UemCommandsCards`](UemCommandsCards)
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{mock::UemReaderMock, worker::*};
+/// # use uem_reader::commands::cards::UemActivateParameters;
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x75, 0x00, 0xAA, 0x80], &[0x04, 0x00, 0x08, 0x04, 0x01, 0x02, 0x03, 0x04]);
+/// # let uem_worker = UemReaderWorker::new(uem_mock.reader());
+/// let card = uem_worker.commands().cards()
+///     .activate_a(&UemActivateParameters::default());
+/// # assert_eq!(card.wait().unwrap().uid, vec![0x01, 0x02, 0x03, 0x04]);
+/// # drop(uem_worker);
+/// # uem_mock.assert_done();
+/// ```
+pub struct UemCommandsCardsWorker<'a> {
+    worker: &'a UemReaderWorker,
+    parameters: UemJobParameters,
+}
+
+impl<'a> UemCommandsCardsWorker<'a> {
+    /// Queue commands of the group with a `priority`
+    pub fn with_priority(mut self, priority: UemPriority) -> Self {
+        self.parameters.priority = priority;
+        self
+    }
+
+    /// Limit every command of the group by a `deadline`
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.parameters.deadline = Some(deadline);
+        self
+    }
+
+    /// Access Mifare cards commands group
+    pub fn mifare(&self) -> UemCommandsCardsMifareWorker<'a> {
+        UemCommandsCardsMifareWorker {worker: self.worker, parameters: self.parameters}
+    }
+
+    /// Activation of type ISO14443A card,
+    /// see [`activate_a`](UemCommandsCards::activate_a)
+    pub fn activate_a(&self, parameters: &UemActivateParameters) -> UemPending<UemCardIso14443A> {
+        let parameters = *parameters;
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().cards().activate_a(&parameters)
+        })
+    }
+
+    /// Activation of type ISO14443B card,
+    /// see [`activate_b`](UemCommandsCards::activate_b)
+    pub fn activate_b(&self, parameters: &UemActivateParameters) -> UemPending<UemCardIso14443B> {
+        let parameters = *parameters;
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().cards().activate_b(&parameters)
+        })
+    }
+}
+
+/// Worker counterpart of [`UemCommandsCardsMifare`](UemCommandsCardsMifare)
+pub struct UemCommandsCardsMifareWorker<'a> {
+    worker: &'a UemReaderWorker,
+    parameters: UemJobParameters,
+}
+
+impl<'a> UemCommandsCardsMifareWorker<'a> {
+    /// Queue commands of the group with a `priority`
+    pub fn with_priority(mut self, priority: UemPriority) -> Self {
+        self.parameters.priority = priority;
+        self
+    }
+
+    /// Limit every command of the group by a `deadline`
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.parameters.deadline = Some(deadline);
+        self
+    }
+
+    /// Access Mifare Classic cards commands group
+    pub fn classic(&self) -> UemCommandsCardsMifareClassicWorker<'a> {
+        UemCommandsCardsMifareClassicWorker {worker: self.worker, parameters: self.parameters}
+    }
+}
+
+/// Worker counterpart of [`UemCommandsCardsMifareClassic`](UemCommandsCardsMifareClassic)
+///
+/// Authentication state of a card may be lost if other
+/// commands run between separate jobs, so dependent commands
+/// are better queued as a single job with
+/// [`run`](UemCommandsWorker::run).
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{mock::UemReaderMock, worker::*};
+/// # use uem_reader::card::UemCardIso14443A;
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x14, 0x60, 0x01, 0x02, 0x03, 0x04, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x04], &[]);
+/// # uem_mock.expect(&[0x19, 0x05], &[0x00; 16]);
+/// # let uem_worker = UemReaderWorker::new(uem_mock.reader());
+/// # let card = UemCardIso14443A { atq: vec![0x04, 0x00], sak: 0x08, uid: vec![0x01, 0x02, 0x03, 0x04], ats: vec![] };
+/// let classic = uem_worker.commands().cards().mifare().classic();
+/// if classic.authenticate_key_a(&card, &[0xFF; 6], 1).wait().is_ok() {
+///     // Read sector 1, block 1
+///     let data = classic.read(1, 1).wait();
+/// #   assert_eq!(data.unwrap().len(), 16);
+/// }
+/// # drop(uem_worker);
+/// # uem_mock.assert_done();
+/// ```
+pub struct UemCommandsCardsMifareClassicWorker<'a> {
+    worker: &'a UemReaderWorker,
+    parameters: UemJobParameters,
+}
+
+impl<'a> UemCommandsCardsMifareClassicWorker<'a> {
+    /// Queue commands of the group with a `priority`
+    pub fn with_priority(mut self, priority: UemPriority) -> Self {
+        self.parameters.priority = priority;
+        self
+    }
+
+    /// Limit every command of the group by a `deadline`
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.parameters.deadline = Some(deadline);
+        self
+    }
+
+    /// Authenticate sector with key A,
+    /// see [`authenticate_key_a`](UemCommandsCardsMifareClassic::authenticate_key_a)
+    pub fn authenticate_key_a(&self, card: &UemCardIso14443A, key: &[u8; 6], sector: u8) -> UemPending<()> {
+        let (card, key) = (card.clone(), *key);
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().cards().mifare().classic()
+                .authenticate_key_a(&card, &key, sector)
+        })
+    }
+
+    /// Authenticate sector with key B,
+    /// see [`authenticate_key_b`](UemCommandsCardsMifareClassic::authenticate_key_b)
+    pub fn authenticate_key_b(&self, card: &UemCardIso14443A, key: &[u8; 6], sector: u8) -> UemPending<()> {
+        let (card, key) = (card.clone(), *key);
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().cards().mifare().classic()
+                .authenticate_key_b(&card, &key, sector)
+        })
+    }
+
+    /// Read card block,
+    /// see [`read`](UemCommandsCardsMifareClassic::read)
+    pub fn read(&self, sector: u8, block: u8) -> UemPending<Vec<u8>> {
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().cards().mifare().classic()
+                .read(sector, block)
+        })
+    }
+
+    /// Write to card block,
+    /// see [`write`](UemCommandsCardsMifareClassic::write)
+    pub fn write(&self, data: Vec<u8>, sector: u8, block: u8) -> UemPending<()> {
+        self.worker.submit(&self.parameters, move |session| {
+            session.commands().cards().mifare().classic()
+                .write(data, sector, block)
+        })
+    }
+}
@@ -0,0 +1,297 @@
+//! Virtual MicroEM reader
+//!
+//! The emulator speaks the same framed protocol as
+//! real readers and answers reader control, card activation
+//! and Mifare Classic commands from a virtual device state.
+//! It can be served over any byte stream, e.g. a pseudo-terminal
+//! (see `uem-emulator` binary) or a TCP connection.
+
+use std::io::{Read, Write};
+
+use crate::errors::*;
+use crate::reader::processing::*;
+
+/// Number of blocks on Mifare Classic 1K card
+const MIFARE_CLASSIC_1K_BLOCKS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Virtual Mifare Classic 1K card in the reader field
+pub struct UemEmulatorCard {
+    /// Answer to request - 2 bytes
+    pub atq: Vec<u8>,
+    /// Select Acknowledge byte
+    pub sak: u8,
+    /// Unique identifier - 4 bytes
+    pub uid: Vec<u8>,
+    /// Card memory, 16 bytes per block.
+    /// Keys are taken from sector trailers.
+    pub blocks: Vec<[u8; 16]>,
+}
+
+impl Default for UemEmulatorCard {
+    fn default() -> UemEmulatorCard {
+        let mut blocks = vec![[0u8; 16]; MIFARE_CLASSIC_1K_BLOCKS];
+        // Transport configuration: FF... keys, default access bits
+        for trailer in blocks.iter_mut().skip(3).step_by(4) {
+            *trailer = [
+                0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0xFF, 0x07, 0x80, 0x69,
+                0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ];
+        }
+        UemEmulatorCard {
+            atq: vec![0x04, 0x00],
+            sak: 0x08,
+            uid: vec![0x01, 0x02, 0x03, 0x04],
+            blocks,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Virtual reader state
+pub struct UemEmulatorState {
+    /// Device address on a multi-drop line,
+    /// frames for other addresses are ignored
+    pub address: u8,
+    /// Reader version - 6 bytes
+    pub version: Vec<u8>,
+    /// Reader serial - 4 bytes
+    pub serial: Vec<u8>,
+    /// Card in the reader field, if any
+    pub card: Option<UemEmulatorCard>,
+    /// Radio field is switched on
+    pub radio: bool,
+}
+
+impl Default for UemEmulatorState {
+    fn default() -> UemEmulatorState {
+        UemEmulatorState {
+            address: 0x00,
+            version: vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x00],
+            serial: vec![0x00, 0x00, 0x00, 0x01],
+            card: Some(UemEmulatorCard::default()),
+            radio: true,
+        }
+    }
+}
+
+/// Virtual reader answering protocol frames
+///
+/// # Example
+///
+/// ```
+/// # use std::net::TcpListener;
+/// # use uem_reader::emulator::*;
+/// # use uem_reader::reader::{UemReaderInternalTrait, tcp::*};
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap().to_string();
+/// std::thread::spawn(move || {
+///     let (stream, _) = listener.accept().unwrap();
+///     let mut uem_emulator = UemEmulator::new(UemEmulatorState {
+///         serial: vec![0x12, 0x34, 0x56, 0x78],
+///         ..Default::default()
+///     });
+///     uem_emulator.serve(stream).ok();
+/// });
+///
+/// let mut uem_reader = new_tcp_reader(&address, &UemTcpParameters::default());
+/// if uem_reader.open().is_err() {
+///     return;
+/// }
+///
+/// let serial = uem_reader.commands().reader().get_serial();
+/// assert_eq!(serial.unwrap(), vec![0x12, 0x34, 0x56, 0x78]);
+/// # uem_reader.close().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct UemEmulator {
+    /// Current virtual reader state
+    pub state: UemEmulatorState,
+    authenticated_sector: Option<u8>,
+    assembler: FrameAssembler,
+}
+
+impl UemEmulator {
+    /// Create an emulator with specific [state](UemEmulatorState)
+    pub fn new(state: UemEmulatorState) -> Self {
+        UemEmulator {
+            state,
+            authenticated_sector: None,
+            assembler: FrameAssembler::default(),
+        }
+    }
+
+    /// Execute a single command
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Command bytes, starting with a command code
+    ///
+    /// # Returns
+    ///
+    /// Response bytes: command code, status byte and payload.
+    pub fn process_command(&mut self, command: &[u8]) -> Vec<u8> {
+        let code = match command.first() {
+            Some(c) => *c,
+            None => return vec![0x00, UemInternalError::ParameterValue as u8],
+        };
+        let mut response = vec![code];
+        match self.execute(command) {
+            Ok(data) => {
+                response.push(0x00);
+                response.extend_from_slice(&data);
+            },
+            Err(error) => response.push(error as u8),
+        }
+        response
+    }
+
+    /// Answer a complete command frame
+    ///
+    /// # Returns
+    ///
+    /// Response frame, or `None` if the frame is
+    /// damaged or addressed to another device.
+    pub fn process_frame(&mut self, frame: &[u8]) -> Option<Vec<u8>> {
+        let (address, counter, command) = parse_command(frame).ok()?;
+        if address != self.state.address {
+            return None;
+        }
+        let response = self.process_command(&command);
+        Some(prepare_response(address, counter, &response))
+    }
+
+    /// Feed bytes received from a host
+    ///
+    /// # Returns
+    ///
+    /// Response frames to send back to the host.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut responses = vec![];
+        for byte in bytes {
+            match self.assembler.push(&[*byte]) {
+                Ok(true) => {
+                    let frame = self.assembler.take();
+                    if let Some(response) = self.process_frame(&frame) {
+                        responses.push(response);
+                    }
+                },
+                Ok(false) => {},
+                Err(_) => { self.assembler.take(); },
+            }
+        }
+        responses
+    }
+
+    /// Answer commands arriving from a byte stream
+    /// until the stream is closed
+    pub fn serve(&mut self, mut stream: impl Read + Write) -> std::io::Result<()> {
+        let mut buffer = [0u8; 256];
+        loop {
+            let count = stream.read(&mut buffer)?;
+            if count == 0 {
+                return Ok(());
+            }
+            for response in self.feed(&buffer[..count]) {
+                stream.write_all(&response)?;
+            }
+            stream.flush()?;
+        }
+    }
+
+    fn execute(&mut self, command: &[u8]) -> core::result::Result<Vec<u8>, UemInternalError> {
+        match command[0] {
+            // Beep, LED, radio power
+            0x05 | 0x07 => Ok(vec![]),
+            0x10 => {
+                self.state.radio = true;
+                Ok(vec![])
+            },
+            0x04 => {
+                self.state.radio = false;
+                self.authenticated_sector = None;
+                Ok(vec![])
+            },
+            0x64 => Ok(self.state.version.clone()),
+            0x22 => Ok(self.state.serial.clone()),
+            0x75 => self.activate(command),
+            0x14 => self.authenticate(command),
+            0x19 => {
+                let block = self.block(command)?;
+                Ok(block.to_vec())
+            },
+            0x1A => {
+                if command.len() != 18 {
+                    return Err(UemInternalError::ParameterValue);
+                }
+                let data: [u8; 16] = command[2..18].try_into()
+                    .map_err(|_| UemInternalError::ParameterValue)?;
+                *self.block(command)? = data;
+                Ok(vec![])
+            },
+            _ => Err(UemInternalError::UnknownCommand),
+        }
+    }
+
+    fn activate(&mut self, command: &[u8]) -> core::result::Result<Vec<u8>, UemInternalError> {
+        self.authenticated_sector = None;
+        if command.len() < 4 {
+            return Err(UemInternalError::ParameterValue);
+        }
+        // Only ISO14443A cards are emulated
+        if command[1] & 0b_0001_0000 != 0 {
+            return Err(UemInternalError::NoTag);
+        }
+        let card = match (&self.state.card, self.state.radio) {
+            (Some(c), true) => c,
+            _ => return Err(UemInternalError::NoTag),
+        };
+        let mut response = card.atq.clone();
+        response.push(card.sak);
+        response.push(card.uid.len() as u8);
+        response.extend_from_slice(&card.uid);
+        Ok(response)
+    }
+
+    fn authenticate(&mut self, command: &[u8]) -> core::result::Result<Vec<u8>, UemInternalError> {
+        self.authenticated_sector = None;
+        if command.len() != 13 {
+            return Err(UemInternalError::ParameterValue);
+        }
+        let card = self.state.card.as_ref().ok_or(UemInternalError::NoTag)?;
+        let address = command[12] as usize;
+        if address >= card.blocks.len() {
+            return Err(UemInternalError::ParameterValue);
+        }
+        let uid = &card.uid[card.uid.len().saturating_sub(4)..];
+        if uid != &command[2..6] {
+            return Err(UemInternalError::NoTag);
+        }
+        let trailer = &card.blocks[address | 0x03];
+        let key = match command[1] {
+            0x60 => &trailer[0..6],
+            0x61 => &trailer[10..16],
+            _ => return Err(UemInternalError::ParameterValue),
+        };
+        if key != &command[6..12] {
+            return Err(UemInternalError::WrongKey);
+        }
+        self.authenticated_sector = Some((address / 4) as u8);
+        Ok(vec![])
+    }
+
+    fn block(&mut self, command: &[u8]) -> core::result::Result<&mut [u8; 16], UemInternalError> {
+        if command.len() < 2 {
+            return Err(UemInternalError::ParameterValue);
+        }
+        let address = command[1];
+        let sector = self.authenticated_sector.ok_or(UemInternalError::NotAuthenticated)?;
+        if address / 4 != sector {
+            return Err(UemInternalError::NotAuthenticated);
+        }
+        let card = self.state.card.as_mut().ok_or(UemInternalError::NoTag)?;
+        card.blocks.get_mut(address as usize).ok_or(UemInternalError::ParameterValue)
+    }
+}
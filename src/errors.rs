@@ -1,5 +1,8 @@
 //! Crate error types
 
+use alloc::string::String;
+use alloc::vec::Vec;
+
 use enum_iterator::{all, Sequence};
 use thiserror::Error;
 
@@ -36,12 +39,20 @@ pub enum UemError {
     #[error("Incorrect reader name")]
     /// The supplied reader name is incorrect
     IncorrectReaderName,
+    #[error("Incorrect reader configuration: {0}")]
+    /// Reader configuration is incomplete or cannot be
+    /// parsed, the message points to the problem
+    IncorrectConfiguration(String),
     #[error("Failed to connect to the reader")]
     /// Reader connection has been unsuccessful
     ReaderConnectionFailed,
     #[error("Reader not connected")]
     /// Reader is not in connected state
     ReaderNotConnected,
+    #[error("Reader disconnected")]
+    /// Reader has been physically
+    /// disconnected from the system
+    ReaderDisconnected,
     #[error("Reader already connected")]
     /// Reader is already connected
     ReaderAlreadyConnected,
@@ -53,6 +64,16 @@ pub enum UemError {
     /// Waiting for a reader response
     /// timed out
     ReaderResponseFailure,
+    #[error("Reader response timed out")]
+    /// A reader did not complete its response
+    /// within the configured timeout
+    ReaderTimeout,
+    #[error("Reader lock poisoned")]
+    /// A thread panicked while using the reader,
+    /// so the reader state is unknown. The reader
+    /// should be reopened, or its mutex poison flag
+    /// cleared before further use
+    ReaderPoisoned,
     #[error("Reader returned error code")]
     /// There is an internal error code received
     /// from a reader, followed by response vector (optional). 
@@ -1,59 +1,53 @@
 //! Crate helpers
 
-pub(crate) fn crc16_ex(buf: &[u8], start: usize, count: usize) -> Vec<u8> {
-    let mut crc: u16 = 0xFFFF;
+use alloc::vec::Vec;
 
-    for pos in start..start + count {
-        crc ^= buf[pos] as u16 & 0x00FF_u16;   // XOR byte into least sig. byte of crc
+pub(crate) const CRC16_INIT: u16 = 0xFFFF;
 
-        for _ in 0..8 {    // Loop over each bit
-            if (crc & 0x0001) != 0 {      // If the LSB is set
-                crc >>= 1;                    // Shift right and XOR 0x8408
-                crc ^= 0x8408;
-            } else {                           // Else LSB is not set
-                crc >>= 1;                    // Just shift right
-            }
+pub(crate) fn crc16_update(mut crc: u16, byte: u8) -> u16 {
+    crc ^= byte as u16 & 0x00FF_u16;   // XOR byte into least sig. byte of crc
+
+    for _ in 0..8 {    // Loop over each bit
+        if (crc & 0x0001) != 0 {      // If the LSB is set
+            crc >>= 1;                    // Shift right and XOR 0x8408
+            crc ^= 0x8408;
+        } else {                           // Else LSB is not set
+            crc >>= 1;                    // Just shift right
         }
     }
-
-    vec![((crc ^ 0xFFFF_u16) & 0x00FF_u16) as u8, 
-    (((crc ^ 0xFFFF_u16) >> 8) & 0x00FF_u16) as u8]
+    crc
 }
 
-pub(crate) fn crc16(buf: &[u8]) -> Vec<u8> {
-    let buf_len = buf.len();
-    crc16_ex(buf, 0, buf_len)
+pub(crate) fn crc16_finish(crc: u16) -> [u8; 2] {
+    [((crc ^ 0xFFFF_u16) & 0x00FF_u16) as u8, 
+    (((crc ^ 0xFFFF_u16) >> 8) & 0x00FF_u16) as u8]
 }
 
-pub(crate) fn byte_stuff(data: &[u8]) -> Vec<u8> {
-    let mut stuffed_data: Vec<u8> = vec![];
-    for data_byte in data {
-        if (data_byte & 0xFF) < 0xFD {
-            stuffed_data.push(*data_byte);
-        } else {
-            stuffed_data.push(0xFF);
-            stuffed_data.push(0xFF - (data_byte & 0xFF));
-        }
+pub(crate) fn stuff_byte(data_byte: u8, stuffed_data: &mut Vec<u8>) {
+    if data_byte < 0xFD {
+        stuffed_data.push(data_byte);
+    } else {
+        stuffed_data.push(0xFF);
+        stuffed_data.push(0xFF - data_byte);
     }
-    return stuffed_data;
 }
 
-pub(crate) fn unbyte_stuff(stuffed_data: &[u8]) -> Vec<u8> {
-    let mut data: Vec<u8> = vec![];
+/// Remove byte stuffing in place,
+/// returns length of unstuffed data
+pub(crate) fn unbyte_stuff_in_place(data: &mut [u8]) -> usize {
+    let mut length = 0;
     let mut invert_next = false;
-    for data_byte in stuffed_data {
-        if (data_byte & 0xFF) == 0xFF {
+    for pos in 0..data.len() {
+        let data_byte = data[pos];
+        if data_byte == 0xFF {
             invert_next = true;
             continue;
         }
-        if invert_next {
-            data.push(0xFF - (data_byte & 0xFF));
-            invert_next = false;
-        } else {
-            data.push(data_byte & 0xFF);
-        }
+        data[length] = if invert_next { 0xFF - data_byte } else { data_byte };
+        invert_next = false;
+        length += 1;
     }
-    return data;
+    length
 }
 
 pub(crate) fn get_absolute_block_address(sector: u8, block: u8) -> u8 {
@@ -1,11 +1,19 @@
-#![doc = include_str!("../README.md")]
+// Usage example of README needs USB readers of `std` feature
+#![cfg_attr(feature = "std", doc = include_str!("../README.md"))]
 
 #![crate_type = "lib"]
 #![crate_name = "uem_reader"]
 #![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
 pub mod errors;
 mod helpers;
 pub mod card;
+pub mod protocol;
+#[cfg(feature = "std")]
 pub mod reader;
+#[cfg(feature = "std")]
 pub mod commands;
+#[cfg(feature = "std")]
+pub mod emulator;
@@ -1,14 +1,8 @@
 // test sequentially using cargo test -- --test-threads 1
 
-mod errors;
-mod helpers;
-mod card;
-mod reader;
-mod commands;
-
-use reader::*;
-use commands::*;
-use commands::{
+use uem_reader::reader::*;
+use uem_reader::commands::*;
+use uem_reader::commands::{
     reader::*, 
     cards::*,
     cards::mifare::*,
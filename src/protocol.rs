@@ -0,0 +1,310 @@
+//! Reader protocol without operating system dependencies
+//!
+//! The module is available without `std` feature,
+//! only `alloc` is required for error and card objects.
+//! Frames are encoded into and decoded from caller-supplied
+//! buffers, either growing vectors or fixed slices,
+//! so a microcontroller can drive a reader through
+//! a [byte transport](transport::UemTransport) of its own.
+//! [`UemReaderHandle`](handle::UemReaderHandle) then runs
+//! commands without locking or dynamic dispatch.
+
+pub mod command;
+pub mod handle;
+pub mod transport;
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::errors::*;
+use crate::helpers::*;
+
+/// Calculate frame checksum
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::protocol::*;
+/// assert_eq!(crc16(&[0x00, 0x01, 0x64]).len(), 2);
+/// ```
+pub fn crc16(data: &[u8]) -> [u8; 2] {
+    crc16_finish(data.iter().fold(CRC16_INIT, |crc, byte| crc16_update(crc, *byte)))
+}
+
+/// Escape bytes reserved for frame delimiters
+///
+/// Stuffed data is appended to `stuffed`.
+pub fn byte_stuff(data: &[u8], stuffed: &mut Vec<u8>) {
+    for byte in data {
+        stuff_byte(*byte, stuffed);
+    }
+}
+
+/// Escape bytes reserved for frame delimiters
+/// into a fixed buffer
+///
+/// # Returns
+///
+/// `Ok(usize)` with stuffed data length on success,
+/// [`UemError::IncorrectParameter`](UemError::IncorrectParameter)
+/// if the data does not fit into the buffer.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::protocol::*;
+/// let mut stuffed = [0u8; 8];
+/// let length = byte_stuff_to_slice(&[0x01, 0xFD], &mut stuffed).unwrap();
+/// assert_eq!(stuffed[..length], [0x01, 0xFF, 0x02]);
+/// assert_eq!(unbyte_stuff(&mut stuffed[..length]), 2);
+/// ```
+pub fn byte_stuff_to_slice(data: &[u8], stuffed: &mut [u8]) -> core::result::Result<usize, UemError> {
+    let mut writer = SliceWriter { buffer: stuffed, length: 0 };
+    for byte in data {
+        writer.stuff(*byte)?;
+    }
+    Ok(writer.length)
+}
+
+/// Remove byte stuffing in place
+///
+/// # Returns
+///
+/// Length of unstuffed data at the start of the buffer.
+pub fn unbyte_stuff(data: &mut [u8]) -> usize {
+    unbyte_stuff_in_place(data)
+}
+
+/// Encode a protocol frame into a caller-supplied buffer
+///
+/// The buffer is cleared first, its capacity is reused,
+/// so encoding into the same buffer does not allocate
+/// once the buffer has grown large enough.
+///
+/// # Arguments
+///
+/// * `address` - Device address, zero for point-to-point links
+/// * `counter` - Command counter
+/// * `data` - Frame data: a command, or a response
+///   with command code and status byte
+/// * `raw_data` - Buffer to receive the 0xFD..0xFE frame
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::protocol::*;
+/// let mut frame = Vec::with_capacity(64);
+/// encode_frame(0x00, 0x01, &[0x64], &mut frame);
+/// assert_eq!(decode_frame(&mut frame).unwrap(), (0x00, 0x01, 3..4));
+/// assert_eq!(frame[3..4], [0x64]);
+/// ```
+pub fn encode_frame(address: u8, counter: u8, data: &[u8], raw_data: &mut Vec<u8>) {
+    raw_data.clear();
+    raw_data.push(0xFD);
+    let mut fsc = CRC16_INIT;
+    for byte in [address, counter].iter().chain(data) {
+        fsc = crc16_update(fsc, *byte);
+        stuff_byte(*byte, raw_data);
+    }
+    for byte in crc16_finish(fsc) {
+        stuff_byte(byte, raw_data);
+    }
+    raw_data.push(0xFE);
+}
+
+/// Encode a protocol frame into a fixed buffer
+///
+/// A frame takes at most `6 + 2 * (data.len() + 4)` bytes.
+///
+/// # Returns
+///
+/// `Ok(usize)` with frame length on success,
+/// [`UemError::IncorrectParameter`](UemError::IncorrectParameter)
+/// if the frame does not fit into the buffer.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::protocol::*;
+/// let mut frame = [0u8; 16];
+/// let length = encode_frame_to_slice(0x00, 0x01, &[0x64], &mut frame).unwrap();
+/// assert_eq!(decode_frame(&mut frame[..length]).unwrap(), (0x00, 0x01, 3..4));
+/// ```
+pub fn encode_frame_to_slice(address: u8, counter: u8, data: &[u8], raw_data: &mut [u8]) -> core::result::Result<usize, UemError> {
+    let mut writer = SliceWriter { buffer: raw_data, length: 0 };
+    writer.push(0xFD)?;
+    let mut fsc = CRC16_INIT;
+    for byte in [address, counter].iter().chain(data) {
+        fsc = crc16_update(fsc, *byte);
+        writer.stuff(*byte)?;
+    }
+    for byte in crc16_finish(fsc) {
+        writer.stuff(byte)?;
+    }
+    writer.push(0xFE)?;
+    Ok(writer.length)
+}
+
+/// Decode a protocol frame in place
+///
+/// Byte stuffing is removed inside the buffer
+/// and the frame checksum is verified.
+///
+/// # Arguments
+///
+/// * `raw_data` - A complete 0xFD..0xFE frame
+///
+/// # Returns
+///
+/// `Ok((address, counter, data))` on success, where `data` is
+/// the range of frame data within `raw_data`, otherwise returns an error.
+pub fn decode_frame(raw_data: &mut [u8]) -> core::result::Result<(u8, u8, Range<usize>), UemError> {
+    let length = raw_data.len();
+    if length < 2 || raw_data[0] != 0xFD || raw_data[length-1] != 0xFE {
+        return Err(UemError::ReaderUnsuccessful(UemInternalError::Protocol, None));
+    }
+    let length = unbyte_stuff_in_place(&mut raw_data[1..length-1]);
+    if length < 4 {
+        return Err(UemError::ReaderUnsuccessful(UemInternalError::Protocol, None));
+    }
+    let body = &raw_data[1..1+length];
+    let fsc = body[..length-2].iter().fold(CRC16_INIT, |fsc, byte| crc16_update(fsc, *byte));
+    if crc16_finish(fsc)[..] != body[length-2..] {
+        return Err(UemError::ReaderUnsuccessful(UemInternalError::Crc, None));
+    }
+    Ok((body[0], body[1], 3..length-1))
+}
+
+/// Decode a response frame in place and check that it belongs
+/// to the `command`
+///
+/// # Arguments
+///
+/// * `command` - A command the response is expected to
+/// * `raw_data` - A complete 0xFD..0xFE response frame
+///
+/// # Returns
+///
+/// `Ok(&[u8])` with response payload within `raw_data` on success,
+/// [`UemError::ReaderUnsuccessful`](UemError::ReaderUnsuccessful)
+/// if the reader reported an error, otherwise returns an error.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::protocol::*;
+/// let mut frame = [0u8; 16];
+/// let length = encode_frame_to_slice(0x00, 0x01, &[0x22, 0x00, 0x01, 0x02, 0x03, 0x04], &mut frame).unwrap();
+/// let serial = parse_response(&[0x22], &mut frame[..length]).unwrap();
+/// assert_eq!(serial, [0x01, 0x02, 0x03, 0x04]);
+/// ```
+pub fn parse_response<'f>(command: &[u8], raw_data: &'f mut [u8]) -> core::result::Result<&'f [u8], UemError> {
+    if raw_data.len() <= 6 {
+        return Err(UemError::ReaderResponseFailure);
+    }
+    let (_, _, data) = decode_frame(raw_data)?;
+    let response = &raw_data[data];
+    //if (reader != null) && reader.Reader._encryptedMode && (data[0] == 0x00) {
+    //    data = AES.decryptChannel(Arrays.copyOfRange(data, 1, data.length), reader);
+    //}
+
+    if (response.len() < 2) || command.first() != Some(&response[0]) {
+        return Err(UemError::ReaderIncorrectResponse);
+    }
+
+    if response[1] != 0x00 {
+        if response.len() == 2 {
+            return Err(UemError::ReaderUnsuccessful(UemInternalError::from_byte(response[1]), None));
+        }
+        return Err(UemError::ReaderUnsuccessful(UemInternalError::from_byte(response[1]), Some(response[2..].to_vec())));
+    }
+
+    Ok(&response[2..])
+}
+
+/// Decode a command frame the way a reader device does
+///
+/// # Arguments
+///
+/// * `raw_data` - A complete 0xFD..0xFE command frame
+///
+/// # Returns
+///
+/// `Ok((address, counter, command))` on success, otherwise returns an error.
+pub fn parse_command(raw_data: &[u8]) -> core::result::Result<(u8, u8, Vec<u8>), UemError> {
+    let mut raw_data = raw_data.to_vec();
+    let (address, counter, data) = decode_frame(&mut raw_data)?;
+    if data.is_empty() {
+        return Err(UemError::ReaderUnsuccessful(UemInternalError::Protocol, None));
+    }
+    Ok((address, counter, raw_data[data].to_vec()))
+}
+
+/// Encode a response frame the way a reader device does
+///
+/// # Arguments
+///
+/// * `address` - Device address, zero for point-to-point links
+/// * `counter` - Command counter copied from the request
+/// * `data` - Response bytes: command code, status byte
+///   and optional payload
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::protocol::*;
+/// let frame = prepare_response(0x00, 0x01, &[0x64, 0x00]);
+/// assert_eq!(frame[0], 0xFD);
+/// assert_eq!(frame[frame.len() - 1], 0xFE);
+/// ```
+pub fn prepare_response(address: u8, counter: u8, data: &[u8]) -> Vec<u8> {
+    let mut raw_data: Vec<u8> = Vec::with_capacity(6 + 2 * data.len());
+    encode_frame(address, counter, data, &mut raw_data);
+    raw_data
+}
+
+/// Copy response payload into a caller-supplied buffer
+pub(crate) fn copy_response(payload: &[u8], response: &mut [u8]) -> core::result::Result<usize, UemError> {
+    let target = response.get_mut(..payload.len()).ok_or(UemError::IncorrectParameter)?;
+    target.copy_from_slice(payload);
+    Ok(payload.len())
+}
+
+/// Extract device address and command counter
+/// from a raw response frame
+pub(crate) fn response_header(raw_data: &[u8]) -> Option<(u8, u8)> {
+    if raw_data.first() != Some(&0xFD) {
+        return None;
+    }
+    let mut header = [0u8; 4];
+    let stuffed = &raw_data[1..raw_data.len().min(5)];
+    header[..stuffed.len()].copy_from_slice(stuffed);
+    if unbyte_stuff_in_place(&mut header[..stuffed.len()]) < 2 {
+        return None;
+    }
+    Some((header[0], header[1]))
+}
+
+/// Writes bytes into a fixed buffer
+struct SliceWriter<'b> {
+    buffer: &'b mut [u8],
+    length: usize,
+}
+
+impl SliceWriter<'_> {
+    fn push(&mut self, byte: u8) -> core::result::Result<(), UemError> {
+        let target = self.buffer.get_mut(self.length).ok_or(UemError::IncorrectParameter)?;
+        *target = byte;
+        self.length += 1;
+        Ok(())
+    }
+
+    fn stuff(&mut self, byte: u8) -> core::result::Result<(), UemError> {
+        if byte < 0xFD {
+            self.push(byte)
+        } else {
+            self.push(0xFF)?;
+            self.push(0xFF - byte)
+        }
+    }
+}
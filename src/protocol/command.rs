@@ -0,0 +1,297 @@
+//! Command encoders and response decoders
+//!
+//! Encoders build commands of reader, cards and
+//! Mifare Classic [command groups](crate::commands)
+//! in fixed buffers, decoders turn response payloads
+//! into card objects.
+//!
+//! # Example
+//!
+//! ```
+//! # use uem_reader::protocol::{*, command::*};
+//! let command = classic_read(1, 1);
+//! assert_eq!(*command, [0x19, 0x05]);
+//!
+//! let mut frame = [0u8; 32];
+//! let length = encode_frame_to_slice(0x00, 0x01, &command, &mut frame).unwrap();
+//! # assert!(length > 0);
+//! ```
+
+#![allow(dead_code)]
+
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use enum_iterator::Sequence;
+
+use crate::card::*;
+use crate::errors::*;
+use crate::helpers::get_absolute_block_address;
+
+/// Maximum length of an encoded command
+pub const MAX_COMMAND_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Encoded command kept in a fixed buffer
+pub struct UemCommand {
+    data: [u8; MAX_COMMAND_LENGTH],
+    length: usize,
+}
+
+impl UemCommand {
+    /// Make a command of bytes
+    ///
+    /// # Returns
+    ///
+    /// `Ok(UemCommand)` on success,
+    /// [`UemError::IncorrectParameter`](UemError::IncorrectParameter)
+    /// if the command is empty or longer than
+    /// [`MAX_COMMAND_LENGTH`](MAX_COMMAND_LENGTH).
+    pub fn new(bytes: &[u8]) -> core::result::Result<Self, UemError> {
+        if bytes.is_empty() || bytes.len() > MAX_COMMAND_LENGTH {
+            return Err(UemError::IncorrectParameter);
+        }
+        let mut data = [0u8; MAX_COMMAND_LENGTH];
+        data[..bytes.len()].copy_from_slice(bytes);
+        Ok(UemCommand { data, length: bytes.len() })
+    }
+
+    fn from_parts(parts: &[&[u8]]) -> Self {
+        let mut command = UemCommand { data: [0u8; MAX_COMMAND_LENGTH], length: 0 };
+        for part in parts {
+            command.data[command.length..command.length + part.len()].copy_from_slice(part);
+            command.length += part.len();
+        }
+        command
+    }
+}
+
+impl Deref for UemCommand {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data[..self.length]
+    }
+}
+
+impl AsRef<[u8]> for UemCommand {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, PartialEq, Sequence, Clone, Copy)]
+/// LED color combinations
+pub enum UemColor {
+    /// LED is off
+    Off = 0b000,
+    Red = 0b001,
+    Green = 0b010,
+    Blue = 0b100,
+    Yellow = 0b011,
+    Magenta = 0b101,
+    Cyan = 0b110,
+    White = 0b111,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Card activation parameters
+///
+/// This structure can be used to both activate cards
+/// of type ISO14443A and ISO14443B
+pub struct UemActivateParameters {
+    // standard: UemCardStandard,
+    /// Required [baudrate](UemCardBaudrates) of card -> reader channel
+    pub baudrate_card_reader: UemCardBaudrates,
+    /// Required [baudrate](UemCardBaudrates) of reader -> card channel
+    pub baudrate_reader_card: UemCardBaudrates,
+    /// Time in milliseconds to turn radio off before
+    /// requesting next card.
+    pub radio_off_period: u8,
+    /// After a radio field has been turned on,
+    /// it is necessary to give cards little time
+    /// to fully power up. Set in milliseconds.
+    pub pause_after_radio_on: u8,
+    /// Enables cards to be automatically switched to
+    /// T=CL protocol (ISO14443-4)
+    pub switch_to_tcl: bool,
+    /// If switched to T=CL, then which card identifier
+    /// (CID) to use
+    pub tcl_cid: u8,
+    /// For cards of type ISO14443B -
+    /// application family identifier
+    pub btype_afi: u8,
+    /// For cards of type ISO14443B -
+    /// request extended answer to query data
+    pub btype_use_ext_atqb: bool,
+    /// For cards of type ISO14443B -
+    /// number of time slots to use
+    pub btype_time_slots: u8,
+}
+
+impl Default for UemActivateParameters {
+    fn default() -> UemActivateParameters {
+        UemActivateParameters {
+            // standard: UemCardStandard::Iso14443a,
+            baudrate_card_reader: UemCardBaudrates::Baud106kbps,
+            baudrate_reader_card: UemCardBaudrates::Baud106kbps,
+            radio_off_period: 10,
+            pause_after_radio_on: 10,
+            switch_to_tcl: false,
+            tcl_cid: 0x00,
+            btype_afi: 0x00,
+            btype_use_ext_atqb: false,
+            btype_time_slots: 1,
+        }
+    }
+}
+
+/// Beep `count` times, `count` should be positive
+pub fn beep(count: u8) -> core::result::Result<UemCommand, UemError> {
+    if count < 1 {
+        return Err(UemError::IncorrectParameter);
+    }
+    Ok(UemCommand::from_parts(&[&[0x05, count]]))
+}
+
+/// Blink `count` times with `color` and remain with `post_color`
+pub fn led(count: u8, color: UemColor, post_color: UemColor) -> UemCommand {
+    UemCommand::from_parts(&[&[0x07, color as u8, count, post_color as u8]])
+}
+
+/// Turn radio chip on or off
+pub fn power_radio(on: bool) -> UemCommand {
+    match on {
+        true => UemCommand::from_parts(&[&[0x10]]),
+        false => UemCommand::from_parts(&[&[0x04, 0x80, 0x01]]),
+    }
+}
+
+/// Turn radio field off for `duration` milliseconds
+pub fn radio_off_on(duration: u16) -> UemCommand {
+    UemCommand::from_parts(&[&[0x05], &duration.to_le_bytes()])
+}
+
+/// Read reader version
+pub fn get_version() -> UemCommand {
+    UemCommand::from_parts(&[&[0x64]])
+}
+
+/// Read reader serial
+pub fn get_serial() -> UemCommand {
+    UemCommand::from_parts(&[&[0x22]])
+}
+
+fn activation_flags(parameters: &UemActivateParameters) -> (u8, u8) {
+    let mut type_baud: u8 = 0x00;
+    type_baud |= (parameters.baudrate_card_reader as u8) << 2;
+    type_baud |= parameters.baudrate_reader_card as u8;
+    let mut rf_reset: u8 = 0x00;
+    rf_reset |= (parameters.radio_off_period & 0x0F) << 4;
+    rf_reset |= parameters.pause_after_radio_on & 0x0F;
+    (type_baud, rf_reset)
+}
+
+/// Activate ISO14443A card,
+/// see [`card_a_from_response`](card_a_from_response)
+/// to decode the response
+pub fn activate_a(parameters: &UemActivateParameters) -> UemCommand {
+    let (type_baud, rf_reset) = activation_flags(parameters);
+    let mut disable_tcl_cid: u8 = 0x00;
+    disable_tcl_cid |= (!parameters.switch_to_tcl as u8) << 7;
+    disable_tcl_cid |= parameters.tcl_cid & 0x0F;
+    UemCommand::from_parts(&[&[0x75, type_baud, rf_reset, disable_tcl_cid]])
+}
+
+/// Activate ISO14443B card,
+/// see [`card_b_from_response`](card_b_from_response)
+/// to decode the response
+pub fn activate_b(parameters: &UemActivateParameters) -> UemCommand {
+    let (type_baud, rf_reset) = activation_flags(parameters);
+    let type_baud = type_baud | 0b_0001_0000;
+    let mut disable_tcl_cid: u8 = 0x00;
+    disable_tcl_cid |= (parameters.switch_to_tcl as u8) << 7;
+    disable_tcl_cid |= parameters.tcl_cid & 0x0F;
+    let mut param: u8 = 0x00;
+    param |= (parameters.btype_use_ext_atqb as u8) << 4;
+    param |= parameters.btype_time_slots & 0x07;
+    UemCommand::from_parts(&[&[0x75, type_baud, rf_reset, disable_tcl_cid, parameters.btype_afi, param]])
+}
+
+/// Decode ISO14443A card from [activation](activate_a) response payload
+pub fn card_a_from_response(response: &[u8]) -> core::result::Result<UemCardIso14443A, UemError> {
+    if response.len() < 8 {
+        return Err(UemError::ReaderIncorrectResponse);
+    }
+
+    let atq = response[0..2].to_vec();
+    let sak: u8 = response[2];
+    let uid_len = response[3] as usize;
+    let uid = response.get(4 .. 4 + uid_len)
+        .ok_or(UemError::ReaderIncorrectResponse)?.to_vec();
+
+    if response.len() == 4 + uid_len {
+        return Ok(UemCardIso14443A{atq, sak, uid, ats: Vec::new()});
+    }
+
+    let ats_len = response[4 + uid_len] as usize;
+    let ats = response.get(4 + uid_len .. 4 + uid_len + ats_len)
+        .ok_or(UemError::ReaderIncorrectResponse)?.to_vec();
+
+    Ok(UemCardIso14443A{atq, sak, uid, ats})
+}
+
+/// Decode ISO14443B card from [activation](activate_b) response payload
+pub fn card_b_from_response(response: &[u8]) -> core::result::Result<UemCardIso14443B, UemError> {
+    if response.len() < 2 {
+        return Err(UemError::ReaderIncorrectResponse);
+    }
+
+    let mbli = response[0];
+    let atqb_len = response[1] as usize;
+
+    if atqb_len < 12 || response.len() < 2 + atqb_len {
+        return Err(UemError::ReaderIncorrectResponse);
+    }
+
+    Ok(UemCardIso14443B{
+        mbli,
+        pupi: response[3..7].to_vec(),
+        app_data: response[7..11].to_vec(),
+        prot_info: response[11..14].to_vec(),
+        atq: response[2 .. 2 + atqb_len].to_vec(),
+    })
+}
+
+fn classic_authenticate(key_type: u8, uid: &[u8], key: &[u8; 6], sector: u8) -> UemCommand {
+    let uid = &uid[uid.len().saturating_sub(4)..];
+    UemCommand::from_parts(&[&[0x14, key_type], uid, key, &[get_absolute_block_address(sector, 0)]])
+}
+
+/// Authenticate Mifare Classic sector with key A
+///
+/// Last 4 bytes of `uid` are used.
+pub fn classic_authenticate_key_a(uid: &[u8], key: &[u8; 6], sector: u8) -> UemCommand {
+    classic_authenticate(0x60, uid, key, sector)
+}
+
+/// Authenticate Mifare Classic sector with key B
+///
+/// Last 4 bytes of `uid` are used.
+pub fn classic_authenticate_key_b(uid: &[u8], key: &[u8; 6], sector: u8) -> UemCommand {
+    classic_authenticate(0x61, uid, key, sector)
+}
+
+/// Read Mifare Classic block
+pub fn classic_read(sector: u8, block: u8) -> UemCommand {
+    UemCommand::from_parts(&[&[0x19, get_absolute_block_address(sector, block)]])
+}
+
+/// Write 16 bytes of `data` to Mifare Classic block
+pub fn classic_write(data: &[u8], sector: u8, block: u8) -> core::result::Result<UemCommand, UemError> {
+    if data.len() != 16 {
+        return Err(UemError::IncorrectParameter);
+    }
+    Ok(UemCommand::from_parts(&[&[0x1A, get_absolute_block_address(sector, block)], data]))
+}
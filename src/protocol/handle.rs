@@ -0,0 +1,168 @@
+//! Statically dispatched reader handles
+//!
+//! [`UemReaderHandle`](UemReaderHandle) owns its
+//! [transport](crate::protocol::transport::UemTransport) and runs
+//! commands of reader, cards and Mifare Classic groups without
+//! locking or dynamic dispatch. It suits single-threaded
+//! firmware, where a reader is used from one place only.
+//! Shared reader objects are created with
+//! [`new_transport_reader`](crate::protocol::transport::new_transport_reader)
+//! when `std` feature is enabled.
+
+use alloc::vec::Vec;
+
+use crate::card::*;
+use crate::errors::*;
+use crate::protocol::command::{self, *};
+use crate::protocol::transport::*;
+
+/// Reader handle owning a [byte transport](UemTransport)
+///
+/// Frames and responses are kept in buffers of `N` bytes
+/// on the stack, see [`UemTransportReader`](UemTransportReader).
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::protocol::{command::*, handle::*, transport::*};
+/// # let transport = UemLoopbackTransport::new(|command| match command {
+/// #     [0x22] => vec![0x22, 0x00, 0x12, 0x34, 0x56, 0x78],
+/// #     _ => vec![command[0], 0x00],
+/// # });
+/// let mut uem_reader: UemReaderHandle<_> = UemReaderHandle::new(transport);
+///
+/// uem_reader.led(1, UemColor::Green, UemColor::Off).unwrap();
+///
+/// let mut serial = [0u8; 4];
+/// assert_eq!(uem_reader.get_serial_into(&mut serial).unwrap(), 4);
+/// assert_eq!(serial, [0x12, 0x34, 0x56, 0x78]);
+/// ```
+pub struct UemReaderHandle<T: UemTransport, const N: usize = DEFAULT_FRAME_BUFFER> {
+    reader: UemTransportReader<T, N>,
+}
+
+impl<T: UemTransport, const N: usize> From<UemTransportReader<T, N>> for UemReaderHandle<T, N> {
+    fn from(reader: UemTransportReader<T, N>) -> Self {
+        UemReaderHandle { reader }
+    }
+}
+
+impl<T: UemTransport, const N: usize> UemReaderHandle<T, N> {
+    /// Create a reader handle on top of a `transport`
+    pub fn new(transport: T) -> Self {
+        UemTransportReader::new(transport).into()
+    }
+
+    /// Protocol state of the handle, e.g. to change
+    /// the command counter
+    pub fn transport_reader(&mut self) -> &mut UemTransportReader<T, N> {
+        &mut self.reader
+    }
+
+    /// Get the transport back
+    pub fn release(self) -> T {
+        self.reader.release()
+    }
+
+    /// Send a command and write response payload
+    /// into a caller-supplied buffer
+    ///
+    /// # Returns
+    ///
+    /// `Ok(usize)` with response length on success,
+    /// [`UemError::IncorrectParameter`](UemError::IncorrectParameter)
+    /// if the response does not fit into the buffer,
+    /// otherwise returns an error.
+    pub fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> core::result::Result<usize, UemError> {
+        self.reader.send_into(command, response)
+    }
+
+    /// Send a command and receive response payload
+    pub fn send(&mut self, command: &[u8]) -> core::result::Result<Vec<u8>, UemError> {
+        let mut response = [0u8; N];
+        let length = self.reader.send_into(command, &mut response)?;
+        Ok(response[..length].to_vec())
+    }
+
+    /// Send a command ignoring response payload
+    fn execute(&mut self, command: &[u8]) -> core::result::Result<(), UemError> {
+        let mut response = [0u8; N];
+        self.reader.send_into(command, &mut response).map(|_| ())
+    }
+
+    /// Beep `count` times, `count` should be positive
+    pub fn beep(&mut self, count: u8) -> core::result::Result<(), UemError> {
+        self.execute(&command::beep(count)?)
+    }
+
+    /// Blink `count` times with `color` and remain with `post_color`
+    pub fn led(&mut self, count: u8, color: UemColor, post_color: UemColor) -> core::result::Result<(), UemError> {
+        self.execute(&command::led(count, color, post_color))
+    }
+
+    /// Turn radio chip on or off
+    pub fn power_radio(&mut self, on: bool) -> core::result::Result<(), UemError> {
+        self.execute(&command::power_radio(on))
+    }
+
+    /// Turn radio field off for `duration` milliseconds
+    pub fn radio_off_on(&mut self, duration: u16) -> core::result::Result<(), UemError> {
+        self.execute(&command::radio_off_on(duration))
+    }
+
+    /// Read reader version into a caller-supplied buffer
+    pub fn get_version_into(&mut self, version: &mut [u8]) -> core::result::Result<usize, UemError> {
+        self.send_into(&command::get_version(), version)
+    }
+
+    /// Read reader serial into a caller-supplied buffer
+    pub fn get_serial_into(&mut self, serial: &mut [u8]) -> core::result::Result<usize, UemError> {
+        self.send_into(&command::get_serial(), serial)
+    }
+
+    /// Activate ISO14443A card
+    pub fn activate_a(&mut self, parameters: &UemActivateParameters) -> core::result::Result<UemCardIso14443A, UemError> {
+        let mut response = [0u8; N];
+        let length = self.send_into(&command::activate_a(parameters), &mut response)?;
+        card_a_from_response(&response[..length])
+    }
+
+    /// Activate ISO14443B card
+    pub fn activate_b(&mut self, parameters: &UemActivateParameters) -> core::result::Result<UemCardIso14443B, UemError> {
+        let mut response = [0u8; N];
+        let length = self.send_into(&command::activate_b(parameters), &mut response)?;
+        card_b_from_response(&response[..length])
+    }
+
+    /// Authenticate Mifare Classic sector of a `card` with key A
+    pub fn classic_authenticate_key_a(&mut self, card: &UemCardIso14443A, key: &[u8; 6], sector: u8) -> core::result::Result<(), UemError> {
+        self.execute(&command::classic_authenticate_key_a(&card.uid, key, sector))
+    }
+
+    /// Authenticate Mifare Classic sector of a `card` with key B
+    pub fn classic_authenticate_key_b(&mut self, card: &UemCardIso14443A, key: &[u8; 6], sector: u8) -> core::result::Result<(), UemError> {
+        self.execute(&command::classic_authenticate_key_b(&card.uid, key, sector))
+    }
+
+    /// Read Mifare Classic block into a caller-supplied buffer
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` on success,
+    /// [`UemError::ReaderIncorrectResponse`](UemError::ReaderIncorrectResponse)
+    /// if the block is not 16 bytes long, otherwise returns an error.
+    pub fn classic_read_into(&mut self, sector: u8, block: u8, data: &mut [u8; 16]) -> core::result::Result<(), UemError> {
+        let mut response = [0u8; N];
+        let length = self.send_into(&command::classic_read(sector, block), &mut response)?;
+        if length != 16 {
+            return Err(UemError::ReaderIncorrectResponse);
+        }
+        data.copy_from_slice(&response[..length]);
+        Ok(())
+    }
+
+    /// Write 16 bytes of `data` to Mifare Classic block
+    pub fn classic_write(&mut self, data: &[u8], sector: u8, block: u8) -> core::result::Result<(), UemError> {
+        self.execute(&command::classic_write(data, sector, block)?)
+    }
+}
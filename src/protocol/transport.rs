@@ -0,0 +1,264 @@
+//! Byte transports
+//!
+//! A [transport](UemTransport) moves raw bytes to and from
+//! a reader, e.g. over a UART of a microcontroller.
+//! [`UemTransportReader`](UemTransportReader) runs the protocol
+//! on top of it using fixed buffers only.
+//!
+//! With `embedded-io` feature any serial port implementing
+//! `embedded_io` traits, as provided by embedded-hal 1.0
+//! drivers, can be used through [`UemIoTransport`](UemIoTransport).
+
+use alloc::vec::Vec;
+
+use crate::errors::*;
+use crate::protocol::*;
+
+/// Default size of frame buffer of a transport reader,
+/// large enough for a Mifare Classic block response
+pub const DEFAULT_FRAME_BUFFER: usize = 128;
+
+/// Minimal byte transport to a reader
+pub trait UemTransport {
+    /// Write all bytes of a frame
+    fn write(&mut self, data: &[u8]) -> core::result::Result<(), UemError>;
+
+    /// Read available bytes into `buffer`
+    ///
+    /// Blocks until some bytes arrive or the transport
+    /// gives up waiting, which is reported as `Ok(0)`
+    /// or [`UemError::ReaderTimeout`](UemError::ReaderTimeout).
+    fn read(&mut self, buffer: &mut [u8]) -> core::result::Result<usize, UemError>;
+}
+
+/// Reader running the protocol on top of a [byte transport](UemTransport)
+///
+/// Frames are kept in a buffer of `N` bytes, longer
+/// responses fail with
+/// [`UemError::ReaderIncorrectResponse`](UemError::ReaderIncorrectResponse).
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::protocol::{command, transport::*};
+/// // Reader answering serial number requests
+/// let transport = UemLoopbackTransport::new(|command| match command {
+///     [0x22] => vec![0x22, 0x00, 0x12, 0x34, 0x56, 0x78],
+///     _ => vec![command[0], 0x00],
+/// });
+/// let mut uem_reader: UemTransportReader<_> = UemTransportReader::new(transport);
+///
+/// let mut serial = [0u8; 4];
+/// let length = uem_reader.send_into(&command::get_serial(), &mut serial);
+/// assert_eq!(length.unwrap(), 4);
+/// assert_eq!(serial, [0x12, 0x34, 0x56, 0x78]);
+/// ```
+pub struct UemTransportReader<T: UemTransport, const N: usize = DEFAULT_FRAME_BUFFER> {
+    transport: T,
+    address: u8,
+    ncommand: u8,
+    frame: [u8; N],
+}
+
+impl<T: UemTransport, const N: usize> UemTransportReader<T, N> {
+    /// Create a reader object on top of a `transport`
+    pub fn new(transport: T) -> Self {
+        UemTransportReader {
+            transport,
+            address: 0x00,
+            ncommand: 0x00,
+            frame: [0u8; N],
+        }
+    }
+
+    /// Set device address on a multi-drop line
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Set counter of the next command
+    pub fn with_commands_count(mut self, count: u8) -> Self {
+        self.ncommand = count;
+        self
+    }
+
+    /// Counter of the next command
+    pub fn commands_count(&self) -> u8 {
+        self.ncommand
+    }
+
+    /// Get the transport back
+    pub fn release(self) -> T {
+        self.transport
+    }
+
+    /// Send a command and write response payload
+    /// into a caller-supplied buffer
+    ///
+    /// # Returns
+    ///
+    /// `Ok(usize)` with response length on success,
+    /// [`UemError::IncorrectParameter`](UemError::IncorrectParameter)
+    /// if the response does not fit into the buffer,
+    /// otherwise returns an error.
+    pub fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> core::result::Result<usize, UemError> {
+        if command.is_empty() {
+            return Err(UemError::IncorrectParameter);
+        }
+        let counter = self.ncommand;
+        let length = encode_frame_to_slice(self.address, counter, command, &mut self.frame)?;
+        self.ncommand = self.ncommand.wrapping_add(1);
+        self.transport.write(&self.frame[..length])?;
+        let length = self.receive_frame(counter)?;
+        copy_response(parse_response(command, &mut self.frame[..length])?, response)
+    }
+
+    /// Collect the response to a command sent with `counter`,
+    /// frames of other devices and late responses are dropped
+    fn receive_frame(&mut self, counter: u8) -> core::result::Result<usize, UemError> {
+        let mut chunk = [0u8; 32];
+        let mut length = 0;
+        loop {
+            let count = self.transport.read(&mut chunk)?;
+            if count == 0 {
+                return Err(UemError::ReaderTimeout);
+            }
+            for byte in &chunk[..count] {
+                if *byte == 0xFD {
+                    length = 0;
+                } else if length == 0 {
+                    continue;
+                }
+                *self.frame.get_mut(length).ok_or(UemError::ReaderIncorrectResponse)? = *byte;
+                length += 1;
+                if *byte != 0xFE {
+                    continue;
+                }
+                if response_header(&self.frame[..length]) == Some((self.address, counter)) {
+                    // Bytes after the frame belong to no command
+                    return Ok(length);
+                }
+                length = 0;
+            }
+        }
+    }
+}
+
+/// [Byte transport](UemTransport) answering frames
+/// the way a reader device does, for testing without hardware
+///
+/// Every written command frame is decoded and passed to
+/// a `respond` function, which returns response bytes:
+/// command code, status byte and optional payload.
+/// The response frame is then available for reading.
+pub struct UemLoopbackTransport<F: FnMut(&[u8]) -> Vec<u8>> {
+    respond: F,
+    pending: Vec<u8>,
+}
+
+impl<F: FnMut(&[u8]) -> Vec<u8>> UemLoopbackTransport<F> {
+    /// Create a transport answering commands with `respond`
+    pub fn new(respond: F) -> Self {
+        UemLoopbackTransport {
+            respond,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<F: FnMut(&[u8]) -> Vec<u8>> UemTransport for UemLoopbackTransport<F> {
+    fn write(&mut self, data: &[u8]) -> core::result::Result<(), UemError> {
+        let (address, counter, command) = parse_command(data)?;
+        let response = (self.respond)(&command);
+        self.pending.extend(prepare_response(address, counter, &response));
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> core::result::Result<usize, UemError> {
+        let count = self.pending.len().min(buffer.len());
+        buffer[..count].copy_from_slice(&self.pending[..count]);
+        self.pending.drain(..count);
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "std")]
+mod reader {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::reader::*;
+
+    impl<T: UemTransport, const N: usize> UemReaderInternalTrait for UemTransportReader<T, N> {
+        /// Transport is opened by its owner
+        fn open(&mut self) -> UemResult {
+            Ok(())
+        }
+
+        /// Transport is closed by its owner
+        fn close(&mut self) -> UemResult {
+            Ok(())
+        }
+
+        fn send(&mut self, command: &[u8]) -> UemResultVec {
+            let mut response = [0u8; N];
+            let length = self.send_into(command, &mut response)?;
+            Ok(response[..length].to_vec())
+        }
+
+        fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+            UemTransportReader::send_into(self, command, response)
+        }
+
+        fn set_commands_count(&mut self, count: u8) -> UemResult {
+            self.ncommand = count;
+            Ok(())
+        }
+    }
+
+    /// Create a reader object on top of a [byte transport](UemTransport),
+    /// so that it can be used with [command groups](crate::commands)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use uem_reader::protocol::transport::*;
+    /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+    /// # let transport = UemLoopbackTransport::new(|command| vec![command[0], 0x00, 0x01, 0x02]);
+    /// let mut uem_reader = new_transport_reader(transport);
+    ///
+    /// assert!(uem_reader.commands().reader().get_version().is_ok());
+    /// ```
+    pub fn new_transport_reader<T: UemTransport + Send + 'static>(transport: T) -> UemReader {
+        Arc::new(Mutex::new(UemTransportReader::<T>::new(transport)
+            .with_commands_count(initial_commands_count())))
+    }
+}
+
+#[cfg(feature = "std")]
+pub use reader::new_transport_reader;
+
+/// [Byte transport](UemTransport) over a serial port
+/// implementing `embedded_io` traits
+///
+/// The port should time out its reads, otherwise a lost
+/// response blocks the reader forever.
+#[cfg(feature = "embedded-io")]
+pub struct UemIoTransport<P>(pub P);
+
+#[cfg(feature = "embedded-io")]
+impl<P: embedded_io::Read + embedded_io::Write> UemTransport for UemIoTransport<P> {
+    fn write(&mut self, data: &[u8]) -> core::result::Result<(), UemError> {
+        self.0.write_all(data).map_err(|_| UemError::NotTransacted)?;
+        self.0.flush().map_err(|_| UemError::NotTransacted)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> core::result::Result<usize, UemError> {
+        use embedded_io::{Error, ErrorKind};
+        self.0.read(buffer).map_err(|e| match e.kind() {
+            ErrorKind::TimedOut => UemError::ReaderTimeout,
+            _ => UemError::ReaderResponseFailure,
+        })
+    }
+}
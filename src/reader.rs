@@ -3,19 +3,83 @@
 
 pub mod usb;
 pub mod com;
+pub mod tcp;
+pub mod health;
+pub mod mock;
+pub mod fault;
+pub mod record;
+pub mod layer;
+pub mod builder;
+pub mod worker;
+pub mod pool;
+#[cfg(feature = "async")]
+pub mod asynchronous;
 
 use crate::errors::*;
 use crate::commands::*;
 use crate::card::*;
 pub use crate::reader::usb::find_usb_readers;
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::{time::Duration};
+use rand::Rng;
 
 pub(crate) const TIMEOUT: Duration = Duration::from_secs(1);
 
+/// Initial command counter for a new reader object
+///
+/// Counters start from a random number, a reader object
+/// can be set to a known one with
+/// [`set_commands_count`](UemReaderInternalTrait::set_commands_count).
+pub(crate) fn initial_commands_count() -> u8 {
+    rand::thread_rng().gen()
+}
+
 /// General reader type using Arc standard type
+/// 
+/// Reader methods and [command groups](crate::commands) never panic
+/// if another thread panicked while using the reader, they fail with
+/// [`UemError::ReaderPoisoned`](UemError::ReaderPoisoned) instead.
+/// 
+/// # Example
+/// 
+/// ```
+/// # use uem_reader::errors::UemError;
+/// # use uem_reader::reader::mock::UemReaderMock;
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// # let uem_mock = UemReaderMock::new();
+/// let mut uem_reader = uem_mock.reader();
+/// 
+/// let shared_reader = uem_reader.clone();
+/// std::thread::spawn(move || {
+///     let _locked = shared_reader.lock();
+///     panic!("reader thread failed");
+/// }).join().ok();
+/// 
+/// let serial = uem_reader.commands().reader().get_serial();
+/// assert!(matches!(serial, Err(UemError::ReaderPoisoned)));
+/// 
+/// // The reader can be used again once its state is checked
+/// uem_reader.clear_poison();
+/// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+/// assert!(uem_reader.commands().reader().get_serial().is_ok());
+/// # uem_mock.assert_done();
+/// ```
 pub type UemReader = Arc<Mutex<dyn UemReaderInternalTrait+Send>>;
+
+/// Lock a reader object for exclusive use
+/// 
+/// # Returns
+/// 
+/// Locked reader on success, [`UemError::ReaderPoisoned`](UemError::ReaderPoisoned)
+/// if a thread panicked while holding the lock.
+pub(crate) fn lock_reader(reader: &UemReader) -> UemGeneralResult<ReaderGuard<'_>> {
+    reader.lock().map_err(|_| UemError::ReaderPoisoned)
+}
+
+/// Reader object locked for exclusive use
+pub(crate) type ReaderGuard<'a> = MutexGuard<'a, dyn UemReaderInternalTrait + Send + 'static>;
+
 /// Vector of readers discovered using specified method
 //pub type UemReaders = Vec<UemReader>;
 
@@ -24,6 +88,9 @@ type UemGeneralResult<T> = core::result::Result<T, UemError>;
 pub type UemResult = UemGeneralResult<()>;
 /// Library result containing returned vector of bytes
 pub type UemResultVec = UemGeneralResult<Vec<u8>>;
+/// Library result containing number of bytes
+/// written into a caller-supplied buffer
+pub type UemResultLen = UemGeneralResult<usize>;
 /// Library result containing ISO14443a card
 pub type UemResultCardA = UemGeneralResult<UemCardIso14443A>;
 /// Library result containing ISO14443b card
@@ -40,6 +107,57 @@ pub trait UemReaderInternalTrait {
     fn open(&mut self) -> UemResult;
     fn close(&mut self) -> core::result::Result<(), UemError>;
     fn send(&mut self, command: &[u8]) -> UemResultVec;
+
+    /// Set default time to wait for a reader response
+    /// 
+    /// # Returns
+    /// 
+    /// `Ok(())` on success, [`UemError::UnsupportedFeature`](UemError::UnsupportedFeature)
+    /// if the reader timeout cannot be changed.
+    fn set_timeout(&mut self, _timeout: Duration) -> UemResult {
+        Err(UemError::UnsupportedFeature)
+    }
+
+    /// Default time to wait for a reader response
+    fn timeout(&self) -> Duration {
+        TIMEOUT
+    }
+
+    /// Set counter of the next command
+    /// 
+    /// # Returns
+    /// 
+    /// `Ok(())` on success, [`UemError::UnsupportedFeature`](UemError::UnsupportedFeature)
+    /// if the reader does not count commands.
+    fn set_commands_count(&mut self, _count: u8) -> UemResult {
+        Err(UemError::UnsupportedFeature)
+    }
+
+    /// Send a command and write response into a caller-supplied buffer
+    /// 
+    /// Transports implement it without allocating memory,
+    /// by default the response of [`send`](UemReaderInternalTrait::send) is copied.
+    /// 
+    /// # Returns
+    /// 
+    /// `Ok(usize)` with response length on success,
+    /// [`UemError::IncorrectParameter`](UemError::IncorrectParameter)
+    /// if the response does not fit into the buffer,
+    /// otherwise returns an error.
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        let data = self.send(command)?;
+        processing::copy_response(&data, response)
+    }
+
+    /// Send a command with a specific response timeout,
+    /// the default timeout is restored afterwards
+    fn send_with_timeout(&mut self, command: &[u8], timeout: Duration) -> UemResultVec {
+        let default_timeout = self.timeout();
+        self.set_timeout(timeout)?;
+        let res = self.send(command);
+        self.set_timeout(default_timeout).ok();
+        res
+    }
 }
 
 impl UemReaderInternalTrait for UemReader {
@@ -53,7 +171,7 @@ impl UemReaderInternalTrait for UemReader {
     /// }
     /// ```
     fn open(&mut self) -> UemResult {
-        self.lock().unwrap().open()
+        lock_reader(self)?.open()
     }
 
     /// Close opened reader interface 
@@ -66,7 +184,7 @@ impl UemReaderInternalTrait for UemReader {
     /// };
     /// ```
     fn close(&mut self) -> core::result::Result<(), UemError> {
-        self.lock().unwrap().close()
+        lock_reader(self)?.close()
     }
 
     /// Send a command to a reader and receive response
@@ -88,66 +206,214 @@ impl UemReaderInternalTrait for UemReader {
     /// }
     /// ```
     fn send(&mut self, command: &[u8]) -> UemResultVec {
-        self.lock().unwrap().send(command)
+        lock_reader(self)?.send(command)
+    }
+
+    /// Send a command and write response into a caller-supplied buffer
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+    /// # let mut uem_reader = uem_mock.reader();
+    /// let mut serial = [0u8; 4];
+    /// let length = uem_reader.send_into(&[0x22], &mut serial);
+    /// # assert_eq!(length.unwrap(), 4);
+    /// ```
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        lock_reader(self)?.send_into(command, response)
+    }
+
+    /// Set default time to wait for a reader response
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// # use std::time::Duration;
+    /// # use uem_reader::reader::{UemReaderInternalTrait, mock::UemReaderMock};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # let mut uem_reader = uem_mock.reader();
+    /// // Wait longer for slow card operations
+    /// if uem_reader.set_timeout(Duration::from_secs(5)).is_err() {
+    ///     return;
+    /// }
+    /// assert_eq!(uem_reader.timeout(), Duration::from_secs(5));
+    /// ```
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        lock_reader(self)?.set_timeout(timeout)
+    }
+
+    /// Default time to wait for a reader response
+    fn timeout(&self) -> Duration {
+        // Reading a setting is safe even after a panic
+        self.lock().unwrap_or_else(|e| e.into_inner()).timeout()
+    }
+
+    /// Set counter of the next command
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        lock_reader(self)?.set_commands_count(count)
+    }
+
+    /// Send a command with a specific response timeout
+    fn send_with_timeout(&mut self, command: &[u8], timeout: Duration) -> UemResultVec {
+        lock_reader(self)?.send_with_timeout(command, timeout)
     }
 }
 
-pub(crate) mod processing {
-    use crate::{helpers::*, reader::*};
+/// Protocol frames processing
+/// 
+/// Besides host side framing used by reader objects,
+/// the module provides device side functions,
+/// which can be used to build reader stand-ins.
+/// Framing functions are shared with the [`protocol`](crate::protocol)
+/// module available without `std` feature.
+pub mod processing {
+    use crate::reader::*;
+    pub use crate::protocol::{encode_frame, decode_frame, parse_response, parse_command, prepare_response};
+    pub(crate) use crate::protocol::{copy_response, response_header};
+
     pub(crate) trait CommandsCounter {
         fn commands_count(&self) -> u8;
         fn increment_commands(&mut self);
+        /// Device address on a multi-drop line,
+        /// point-to-point links use zero address
+        fn device_address(&self) -> u8 {
+            0x00
+        }
     }
 
-    pub(crate) fn prepare_command(reader: &mut impl CommandsCounter, data: &[u8]) -> Vec<u8> {
-        
-        let mut raw_data: Vec<u8> = vec![];
-
-        raw_data.push(0x00);
-        raw_data.push(reader.commands_count());
-        reader.increment_commands();
-
+    pub(crate) fn prepare_command(reader: &mut impl CommandsCounter, data: &[u8], raw_data: &mut Vec<u8>) {
         //if ((reader != null) && reader.Reader.encryptedMode) {
         //    rawData.write(0x00);
         //    data = AES.encryptChannel(data, reader);
         //    if (data == null)
         //        return null;
         //}
-        let mut tmp_v = vec![];
-        data.clone_into(&mut tmp_v);
-        raw_data.append(&mut tmp_v);
-
-        let mut fsc = crc16(&raw_data);
-        raw_data.append(&mut fsc);
-
-        let mut tmp_data = byte_stuff(&raw_data);
-        let mut raw_data: Vec<u8> = vec![];
-        raw_data.reserve(2 + tmp_data.len());
-        raw_data.push(0xFD);
-        raw_data.append(&mut tmp_data);
-        raw_data.push(0xFE);
-        return raw_data;
-    }
-
-    pub(crate) fn parse_response(raw_data: &Vec<u8>) -> UemResultVec {
-        let raw_data = unbyte_stuff(raw_data);
-        if (raw_data[0] & 0xFF) != 0xFD {
-            return Err(UemError::ReaderUnsuccessful(UemInternalError::Protocol, None));
+        encode_frame(reader.device_address(), reader.commands_count(), data, raw_data);
+        reader.increment_commands();
+    }
+
+    /// Upper limit for a raw response frame length
+    pub(crate) const MAX_FRAME_LENGTH: usize = 0x10000;
+
+    /// Collects a response frame from portions
+    /// of bytes arriving from a transport
+    /// 
+    /// Buffers are kept between frames,
+    /// so a reused assembler does not allocate.
+    #[derive(Debug, Default)]
+    pub(crate) struct FrameAssembler {
+        frame: Vec<u8>,
+        complete: bool,
+        rest: Vec<u8>,
+        spare: Vec<u8>,
+    }
+
+    impl FrameAssembler {
+        /// Feed received bytes into the frame.
+        /// Bytes preceding the 0xFD start byte are dropped,
+        /// a repeated start byte restarts the frame.
+        /// 
+        /// Returns `Ok(true)` once the 0xFE terminator is received.
+        /// Bytes following the terminator are kept for the next frame.
+        pub(crate) fn push(&mut self, bytes: &[u8]) -> core::result::Result<bool, UemError> {
+            for (i, byte) in bytes.iter().enumerate() {
+                if self.complete {
+                    self.rest.extend_from_slice(&bytes[i..]);
+                    break;
+                }
+                if *byte == 0xFD {
+                    self.frame.clear();
+                } else if self.frame.is_empty() {
+                    continue;
+                }
+                if self.frame.len() >= MAX_FRAME_LENGTH {
+                    return Err(UemError::ReaderIncorrectResponse);
+                }
+                self.frame.push(*byte);
+                self.complete = *byte == 0xFE;
+            }
+            Ok(self.complete)
         }
-        if (raw_data[raw_data.len()-1] & 0xFF) != 0xFE {
-            return Err(UemError::ReaderUnsuccessful(UemInternalError::Protocol, None));
+
+        pub(crate) fn is_complete(&self) -> bool {
+            self.complete
         }
-        let fsc = crc16(&raw_data[1..raw_data.len()-3]);
-        if (fsc[0] & 0xFF) != (raw_data[raw_data.len()-3] & 0xFF) {
-            return Err(UemError::ReaderUnsuccessful(UemInternalError::Crc, None));//  Err(UemError::CRC);
+
+        /// Collected frame
+        pub(crate) fn frame_mut(&mut self) -> &mut [u8] {
+            &mut self.frame
         }
-        if  (fsc[1] & 0xFF) != (raw_data[raw_data.len()-2] & 0xFF) {
-            return Err(UemError::ReaderUnsuccessful(UemInternalError::Crc, None));
+
+        /// Drop collected frame and start
+        /// the next one from kept bytes
+        pub(crate) fn discard(&mut self) {
+            self.frame.clear();
+            self.complete = false;
+            std::mem::swap(&mut self.rest, &mut self.spare);
+            let mut rest = std::mem::take(&mut self.spare);
+            if self.push(&rest).is_err() {
+                self.frame.clear();
+            }
+            rest.clear();
+            self.spare = rest;
         }
-        let data = raw_data[3..raw_data.len()-3].to_vec();
-        //if (reader != null) && reader.Reader._encryptedMode && (data[0] == 0x00) {
-        //    data = AES.decryptChannel(Arrays.copyOfRange(data, 1, data.length), reader);
-        //}
-        Ok(data)
+
+        /// Drop all collected bytes
+        pub(crate) fn clear(&mut self) {
+            self.frame.clear();
+            self.rest.clear();
+            self.complete = false;
+        }
+
+        /// Take collected frame out of the assembler
+        /// and start the next one from kept bytes
+        pub(crate) fn take(&mut self) -> Vec<u8> {
+            let frame = std::mem::take(&mut self.frame);
+            self.discard();
+            frame
+        }
+    }
+
+    /// Look for the response to a command sent with `counter`
+    /// to a device with `address`, it is left in the assembler.
+    /// Frames of other devices and late responses to previous
+    /// commands are dropped.
+    pub(crate) fn find_response(assembler: &mut FrameAssembler, address: u8, counter: u8) -> bool {
+        while assembler.is_complete() {
+            if response_header(assembler.frame_mut()) == Some((address, counter)) {
+                return true;
+            }
+            assembler.discard();
+        }
+        false
     }
-}
\ No newline at end of file
+
+    /// Collect the response to a command sent with `counter`
+    /// to a device with `address` from a byte stream.
+    /// The stream read timeout should not exceed `timeout`.
+    pub(crate) fn receive_response(source: &mut impl std::io::Read, assembler: &mut FrameAssembler, timeout: Duration, address: u8, counter: u8) -> UemResult {
+        let deadline = std::time::Instant::now() + timeout;
+        let mut chunk = [0u8; 64];
+        assembler.clear();
+        loop {
+            if std::time::Instant::now() >= deadline {
+                return Err(UemError::ReaderTimeout);
+            }
+            let count = match source.read(&mut chunk) {
+                Ok(0) => return Err(UemError::ReaderResponseFailure),
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) if matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock) =>
+                    return Err(UemError::ReaderTimeout),
+                Err(_) => return Err(UemError::ReaderResponseFailure),
+            };
+            assembler.push(&chunk[..count])?;
+            if find_response(assembler, address, counter) {
+                return Ok(());
+            }
+        }
+    }
+}
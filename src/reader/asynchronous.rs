@@ -0,0 +1,253 @@
+//! Asynchronous reader objects for tokio runtime
+//!
+//! Available with `async` feature. Reader transports
+//! are blocking, so every transaction runs on a blocking
+//! thread of the runtime, while tasks waiting for the reader
+//! are queued on an asynchronous mutex instead of occupying threads.
+//!
+//! Futures of the module are cancellation-safe: a transaction,
+//! once started, owns the reader until it completes. Dropping
+//! its future, e.g. after a timeout, only discards the response,
+//! so the next command never picks up a half-read frame.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use uem_reader::errors::UemError;
+//! # use uem_reader::reader::{mock::UemReaderMock, fault::*, asynchronous::*};
+//! # let uem_mock = UemReaderMock::new();
+//! # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+//! # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+//! # let uem_injector = UemFaultInjector::new(uem_mock.reader(), &UemFaultParameters {
+//! #     delay_time: Duration::from_millis(200),
+//! #     ..Default::default()
+//! # });
+//! # uem_injector.inject(UemFault::Delay);
+//! # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+//! // The first response is late
+//! let uem_reader = UemReaderAsync::new(uem_injector.reader());
+//!
+//! let serial = uem_reader.commands()
+//!     .with_timeout(Duration::from_millis(50))
+//!     .reader().get_serial().await;
+//! assert!(matches!(serial, Err(UemError::ReaderTimeout)));
+//!
+//! // Waits until the abandoned transaction is over
+//! let serial = uem_reader.commands().reader().get_serial().await;
+//! assert_eq!(serial.unwrap(), vec![0x01, 0x02, 0x03, 0x04]);
+//! # });
+//! # uem_mock.assert_done();
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::reader::*;
+use crate::commands::asynchronous::*;
+use crate::commands::session::*;
+
+/// Asynchronous counterpart of
+/// [`UemReaderInternalTrait`](UemReaderInternalTrait)
+pub trait UemReaderAsyncTrait {
+    /// Open reader interface
+    fn open(&self) -> impl Future<Output = UemResult> + Send;
+    /// Close reader interface
+    fn close(&self) -> impl Future<Output = UemResult> + Send;
+    /// Send a command and wait for the response
+    fn send(&self, command: &[u8]) -> impl Future<Output = UemResultVec> + Send;
+    /// Send a command and wait for the response
+    /// not longer than `timeout`
+    ///
+    /// The future completes with
+    /// [`UemError::ReaderTimeout`](UemError::ReaderTimeout)
+    /// when the time is over, the reader itself
+    /// stops waiting for the response after the same time.
+    fn send_with_timeout(&self, command: &[u8], timeout: Duration) -> impl Future<Output = UemResultVec> + Send;
+    /// Set default time to wait for a reader response
+    fn set_timeout(&self, timeout: Duration) -> impl Future<Output = UemResult> + Send;
+    /// Default time to wait for a reader response
+    fn timeout(&self) -> impl Future<Output = Duration> + Send;
+}
+
+/// Reader object shared between asynchronous tasks
+///
+/// Clones of the object refer to the same reader.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{mock::UemReaderMock, asynchronous::*};
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+/// # let uem_reader = uem_mock.reader();
+/// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+/// let uem_reader = UemReaderAsync::new(uem_reader);
+///
+/// let serial = uem_reader.commands().reader().get_serial().await;
+/// assert_eq!(serial.unwrap(), vec![0x01, 0x02, 0x03, 0x04]);
+/// # });
+/// # uem_mock.assert_done();
+/// ```
+#[derive(Clone)]
+pub struct UemReaderAsync {
+    reader: Arc<tokio::sync::Mutex<UemReader>>,
+}
+
+impl UemReaderAsync {
+    /// Wrap a blocking reader object
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A reader object created by any of the transports
+    pub fn new(reader: UemReader) -> Self {
+        UemReaderAsync {
+            reader: Arc::new(tokio::sync::Mutex::new(reader)),
+        }
+    }
+
+    /// Get commands object of the reader
+    pub fn commands(&self) -> UemCommandsAsync<'_> {
+        UemCommandsAsync::new(self)
+    }
+
+    /// Run a blocking `operation` in an [exclusive session](UemSession)
+    ///
+    /// Neither other tasks nor other threads sharing the
+    /// blocking reader object can interrupt the operation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use uem_reader::reader::{mock::UemReaderMock, asynchronous::*};
+    /// # use uem_reader::commands::{UemCommandsTrait, cards::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x75, 0x00, 0xAA, 0x80], &[0x04, 0x00, 0x08, 0x04, 0x01, 0x02, 0x03, 0x04]);
+    /// # uem_mock.expect(&[0x75, 0x00, 0xAA, 0x80], &[0x04, 0x00, 0x08, 0x04, 0x01, 0x02, 0x03, 0x04]);
+    /// # let uem_reader = UemReaderAsync::new(uem_mock.reader());
+    /// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+    /// // Check that the same card stays in the field
+    /// let same_card = uem_reader.with_session(|session| {
+    ///     let first = session.commands().cards().activate_a(&UemActivateParameters::default())?;
+    ///     let second = session.commands().cards().activate_a(&UemActivateParameters::default())?;
+    ///     Ok(first.uid == second.uid)
+    /// }).await;
+    /// # assert!(same_card.unwrap());
+    /// # });
+    /// # uem_mock.assert_done();
+    /// ```
+    pub async fn with_session<T, F>(&self, operation: F) -> UemGeneralResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut UemSession<'_>) -> UemGeneralResult<T> + Send + 'static,
+    {
+        self.run(None, move |reader| reader.with_session(operation)).await
+    }
+
+    /// Run a blocking `operation` on the reader
+    ///
+    /// The operation is started when the reader is free
+    /// and keeps it locked until completion, even if the
+    /// returned future has been dropped. With `timeout`
+    /// the future completes with
+    /// [`UemError::ReaderTimeout`](UemError::ReaderTimeout)
+    /// if the reader is not free or the operation is not
+    /// finished in time.
+    pub(crate) async fn run<T, F>(&self, timeout: Option<Duration>, operation: F) -> UemGeneralResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut UemReader) -> UemGeneralResult<T> + Send + 'static,
+    {
+        let transaction = async {
+            let mut reader = self.reader.clone().lock_owned().await;
+            tokio::task::spawn_blocking(move || operation(&mut reader)).await
+        };
+        let res = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, transaction).await
+                .map_err(|_| UemError::ReaderTimeout)?,
+            None => transaction.await,
+        };
+        // The operation has panicked
+        res.map_err(|_| UemError::Unexpected)?
+    }
+}
+
+impl From<UemReader> for UemReaderAsync {
+    fn from(reader: UemReader) -> Self {
+        UemReaderAsync::new(reader)
+    }
+}
+
+impl UemReaderAsyncTrait for UemReaderAsync {
+    fn open(&self) -> impl Future<Output = UemResult> + Send {
+        self.run(None, |reader| reader.open())
+    }
+
+    fn close(&self) -> impl Future<Output = UemResult> + Send {
+        self.run(None, |reader| reader.close())
+    }
+
+    fn send(&self, command: &[u8]) -> impl Future<Output = UemResultVec> + Send {
+        let command = command.to_vec();
+        self.run(None, move |reader| reader.send(&command))
+    }
+
+    /// # Example
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use uem_reader::reader::{mock::UemReaderMock, asynchronous::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x64], &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    /// # let uem_reader = UemReaderAsync::new(uem_mock.reader());
+    /// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+    /// let version = uem_reader
+    ///     .send_with_timeout(&[0x64], Duration::from_millis(100)).await;
+    /// # assert_eq!(version.unwrap().len(), 6);
+    /// # });
+    /// # uem_mock.assert_done();
+    /// ```
+    fn send_with_timeout(&self, command: &[u8], timeout: Duration) -> impl Future<Output = UemResultVec> + Send {
+        let command = command.to_vec();
+        self.run(Some(timeout), move |reader| reader.send_with_timeout(&command, timeout))
+    }
+
+    fn set_timeout(&self, timeout: Duration) -> impl Future<Output = UemResult> + Send {
+        self.run(None, move |reader| reader.set_timeout(timeout))
+    }
+
+    fn timeout(&self) -> impl Future<Output = Duration> + Send {
+        let run = self.run(None, |reader| Ok(reader.timeout()));
+        async { run.await.unwrap_or(TIMEOUT) }
+    }
+}
+
+/// Find all MicroEM readers connected via USB
+///
+/// Asynchronous counterpart of [`find_usb_readers`](crate::reader::usb::find_usb_readers).
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::asynchronous::*;
+/// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+/// let uem_readers = find_usb_readers().await;
+///
+/// for uem_reader in uem_readers {
+///     if uem_reader.open().await.is_err() {
+///         continue;
+///     }
+///     let serial = uem_reader.commands().reader().get_serial().await;
+///     println!("{:02X?}", serial);
+///     uem_reader.close().await.ok();
+/// }
+/// # });
+/// ```
+pub async fn find_usb_readers() -> Vec<UemReaderAsync> {
+    tokio::task::spawn_blocking(crate::reader::usb::find_usb_readers).await
+        .unwrap_or_default()
+        .into_iter()
+        .map(UemReaderAsync::new)
+        .collect()
+}
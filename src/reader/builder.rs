@@ -0,0 +1,338 @@
+//! Reader objects configured in one place
+//!
+//! [`UemReaderBuilder`](UemReaderBuilder) selects a transport,
+//! applies reader settings and [layers](crate::reader::layer),
+//! and returns an opened reader object. Settings can be
+//! given in code or read from a configuration file.
+
+use std::time::Duration;
+
+use crate::reader::*;
+use crate::reader::{usb::*, com::*, tcp::*, mock::*, layer::*};
+
+#[derive(Clone)]
+enum Transport {
+    Usb,
+    UsbSerial(String),
+    UsbPath(String),
+    Serial(String, UemComParameters, u8),
+    Tcp(String, UemTcpParameters),
+    Mock(UemReader),
+}
+
+/// Builder of opened reader objects
+///
+/// # Example
+///
+/// ```
+/// # use std::time::Duration;
+/// # use uem_reader::reader::{mock::*, builder::*, layer::*};
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+/// let mut uem_reader = UemReaderBuilder::new()
+///     .mock(&uem_mock)
+///     .timeout(Duration::from_millis(500))
+///     .retry(&UemRetryPolicy::default())
+///     .trace(|line| println!("{}", line))
+///     .build()
+///     .unwrap();
+///
+/// assert!(uem_reader.commands().reader().get_serial().is_ok());
+/// # uem_mock.assert_done();
+/// ```
+#[derive(Clone, Default)]
+pub struct UemReaderBuilder {
+    transport: Option<Transport>,
+    timeout: Option<Duration>,
+    retry: Option<UemRetryPolicy>,
+    commands_count: Option<u8>,
+    trace: Option<UemLogLayer>,
+}
+
+impl UemReaderBuilder {
+    /// Create a builder without transport selected
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the first USB reader found
+    pub fn usb(mut self) -> Self {
+        self.transport = Some(Transport::Usb);
+        self
+    }
+
+    /// Use a USB reader with specific serial number,
+    /// see [`open_usb_reader_by_serial`](open_usb_reader_by_serial)
+    pub fn usb_serial(mut self, serial: &str) -> Self {
+        self.transport = Some(Transport::UsbSerial(serial.to_string()));
+        self
+    }
+
+    /// Use a USB reader connected to specific port,
+    /// see [`open_usb_reader_by_path`](open_usb_reader_by_path)
+    pub fn usb_path(mut self, path: &str) -> Self {
+        self.transport = Some(Transport::UsbPath(path.to_string()));
+        self
+    }
+
+    /// Use a reader on a serial line,
+    /// see [`new_rs_reader`](new_rs_reader)
+    pub fn serial(self, path: &str, parameters: &UemComParameters) -> Self {
+        self.serial_bus(path, parameters, 0x00)
+    }
+
+    /// Use a reader with specific `address` on a multi-drop
+    /// serial line, see [`UemComBus`](UemComBus)
+    pub fn serial_bus(mut self, path: &str, parameters: &UemComParameters, address: u8) -> Self {
+        self.transport = Some(Transport::Serial(path.to_string(), *parameters, address));
+        self
+    }
+
+    /// Use a reader behind a network gateway,
+    /// see [`new_tcp_reader`](new_tcp_reader)
+    pub fn tcp(mut self, address: &str, parameters: &UemTcpParameters) -> Self {
+        self.transport = Some(Transport::Tcp(address.to_string(), *parameters));
+        self
+    }
+
+    /// Use a [mock reader](UemReaderMock)
+    pub fn mock(mut self, mock: &UemReaderMock) -> Self {
+        self.transport = Some(Transport::Mock(mock.reader()));
+        self
+    }
+
+    /// Set default time to wait for a reader response
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Repeat failed commands according to a [retry policy](UemRetryPolicy)
+    pub fn retry(mut self, policy: &UemRetryPolicy) -> Self {
+        self.retry = Some(*policy);
+        self
+    }
+
+    /// Set counter of the first command
+    /// instead of a random one
+    pub fn commands_count(mut self, count: u8) -> Self {
+        self.commands_count = Some(count);
+        self
+    }
+
+    /// Pass every transaction as a text line to a `sink`,
+    /// see [`UemLogLayer`](UemLogLayer)
+    pub fn trace(mut self, sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.trace = Some(UemLogLayer::new(sink));
+        self
+    }
+
+    /// Create and open a reader object
+    ///
+    /// # Returns
+    ///
+    /// `Ok(UemReader)` with opened reader on success,
+    /// [`UemError::IncorrectConfiguration`](UemError::IncorrectConfiguration)
+    /// if the configuration is incomplete or not supported
+    /// by the transport, otherwise returns a transport error.
+    pub fn build(&self) -> core::result::Result<UemReader, UemError> {
+        let transport = self.transport.as_ref()
+            .ok_or_else(|| configuration_error("no transport selected"))?;
+        let mut reader = open_transport(transport)?;
+
+        if let Err(e) = self.configure(&mut reader) {
+            reader.close().ok();
+            return Err(e);
+        }
+
+        let mut layers = UemLayers::new();
+        if let Some(policy) = &self.retry {
+            layers = layers.layer(UemRetryLayer::new(policy));
+        }
+        if let Some(trace) = &self.trace {
+            // Below retries, so that every attempt is traced
+            layers = layers.layer(trace.clone());
+        }
+        Ok(layers.wrap(reader))
+    }
+
+    fn configure(&self, reader: &mut UemReader) -> UemResult {
+        if let Some(timeout) = self.timeout {
+            reader.set_timeout(timeout).map_err(|e| match e {
+                UemError::UnsupportedFeature => configuration_error("transport does not support timeout"),
+                e => e,
+            })?;
+        }
+        if let Some(count) = self.commands_count {
+            reader.set_commands_count(count).map_err(|e| match e {
+                UemError::UnsupportedFeature => configuration_error("transport does not support commands_count"),
+                e => e,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Create a builder from configuration text
+    ///
+    /// Configuration consists of `key = value` lines,
+    /// strings may be quoted, `#` outside quotes starts a comment, so
+    /// a flat TOML file can be used. Keys are:
+    ///
+    /// * `transport` - `usb`, `serial` or `tcp`, required
+    /// * `serial` - USB reader serial number
+    /// * `path` - USB port path or serial line path
+    /// * `baudrate`, `parity` (`none`, `odd`, `even`)
+    ///   and `device_address` - serial line settings
+    /// * `address`, `connect_timeout_ms` and `reconnect` - network settings
+    /// * `timeout_ms` - time to wait for a reader response
+    /// * `retry_attempts` and `retry_delay_ms` - [retry policy](UemRetryPolicy)
+    /// * `commands_count` - counter of the first command
+    /// * `trace` - `stdout` or `stderr` to trace transactions
+    ///
+    /// # Returns
+    ///
+    /// `Ok(UemReaderBuilder)` on success,
+    /// [`UemError::IncorrectConfiguration`](UemError::IncorrectConfiguration)
+    /// pointing to the wrong line otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::net::TcpListener;
+    /// # use uem_reader::emulator::*;
+    /// # use uem_reader::reader::builder::*;
+    /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+    /// # let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// # let address = listener.local_addr().unwrap().to_string();
+    /// # std::thread::spawn(move || {
+    /// #     let (stream, _) = listener.accept().unwrap();
+    /// #     UemEmulator::new(UemEmulatorState::default()).serve(stream).ok();
+    /// # });
+    /// let config = format!(r#"
+    ///     transport = "tcp"   # reader behind a network gateway
+    ///     address = "{}"
+    ///     timeout_ms = 500
+    ///     retry_attempts = 3
+    /// "#, address);
+    ///
+    /// let mut uem_reader = UemReaderBuilder::from_config(&config)
+    ///     .and_then(|builder| builder.build())
+    ///     .unwrap();
+    ///
+    /// assert!(uem_reader.commands().reader().get_version().is_ok());
+    /// ```
+    pub fn from_config(text: &str) -> core::result::Result<Self, UemError> {
+        let mut builder = UemReaderBuilder::new();
+        let mut transport = None;
+        let mut serial = None;
+        let mut path = None;
+        let mut address = None;
+        let mut com_parameters = UemComParameters::default();
+        let mut device_address = 0x00;
+        let mut tcp_parameters = UemTcpParameters::default();
+        let mut retry = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let line_error = |problem: &str| configuration_error(&format!("line {}: {}", number + 1, problem));
+            let (key, value) = line.split_once('=').ok_or_else(|| line_error("expected key = value"))?;
+            let (key, value) = (key.trim(), value.trim().trim_matches('"'));
+            let integer = |value: &str| value.parse::<u64>().map_err(|_| line_error("expected number"));
+            let byte = |value: &str| value.parse::<u8>().map_err(|_| line_error("expected number up to 255"));
+            match key {
+                "transport" => transport = Some(value.to_string()),
+                "serial" => serial = Some(value.to_string()),
+                "path" => path = Some(value.to_string()),
+                "address" => address = Some(value.to_string()),
+                "baudrate" => com_parameters.baudrate = value.parse().map_err(|_| line_error("expected number"))?,
+                "parity" => com_parameters.parity = match value {
+                    "none" => UemComParity::None,
+                    "odd" => UemComParity::Odd,
+                    "even" => UemComParity::Even,
+                    _ => return Err(line_error("expected none, odd or even")),
+                },
+                "device_address" => device_address = byte(value)?,
+                "connect_timeout_ms" => tcp_parameters.connect_timeout = Duration::from_millis(integer(value)?),
+                "reconnect" => tcp_parameters.reconnect = value.parse().map_err(|_| line_error("expected true or false"))?,
+                "timeout_ms" => builder.timeout = Some(Duration::from_millis(integer(value)?)),
+                "retry_attempts" => retry.get_or_insert_with(UemRetryPolicy::default).attempts =
+                    value.parse().map_err(|_| line_error("expected number"))?,
+                "retry_delay_ms" => retry.get_or_insert_with(UemRetryPolicy::default).delay =
+                    Duration::from_millis(integer(value)?),
+                "commands_count" => builder.commands_count = Some(byte(value)?),
+                "trace" => builder.trace = Some(match value {
+                    "stdout" => UemLogLayer::new(|line| println!("{}", line)),
+                    "stderr" => UemLogLayer::new(|line| eprintln!("{}", line)),
+                    _ => return Err(line_error("expected stdout or stderr")),
+                }),
+                _ => return Err(line_error(&format!("unknown key {}", key))),
+            }
+        }
+
+        builder.retry = retry;
+        builder.transport = Some(match transport.as_deref() {
+            Some("usb") => match (serial, path) {
+                (Some(serial), _) => Transport::UsbSerial(serial),
+                (None, Some(path)) => Transport::UsbPath(path),
+                (None, None) => Transport::Usb,
+            },
+            Some("serial") => Transport::Serial(
+                path.ok_or_else(|| configuration_error("serial transport requires path"))?,
+                com_parameters, device_address),
+            Some("tcp") => Transport::Tcp(
+                address.ok_or_else(|| configuration_error("tcp transport requires address"))?,
+                tcp_parameters),
+            Some(other) => return Err(configuration_error(&format!("unknown transport {}", other))),
+            None => return Err(configuration_error("transport is not set")),
+        });
+        Ok(builder)
+    }
+
+    /// Create a builder from a configuration file,
+    /// see [`from_config`](UemReaderBuilder::from_config)
+    /// for the file format
+    pub fn from_config_file(path: impl AsRef<std::path::Path>) -> core::result::Result<Self, UemError> {
+        let text = std::fs::read_to_string(path.as_ref()).map_err(|e|
+            configuration_error(&format!("{}: {}", path.as_ref().display(), e)))?;
+        UemReaderBuilder::from_config(&text)
+    }
+}
+
+fn open_transport(transport: &Transport) -> core::result::Result<UemReader, UemError> {
+    let mut reader = match transport {
+        // Readers found by serial or path are opened already
+        Transport::UsbSerial(serial) => return open_usb_reader_by_serial(serial),
+        Transport::UsbPath(path) => return open_usb_reader_by_path(path),
+        Transport::Usb => find_usb_readers().into_iter().next()
+            .ok_or(UemError::IncorrectReaderName)?,
+        Transport::Serial(path, parameters, 0x00) => new_rs_reader(path, parameters),
+        Transport::Serial(path, parameters, address) =>
+            UemComBus::new(path, parameters).reader(*address),
+        Transport::Tcp(address, parameters) => new_tcp_reader(address, parameters),
+        Transport::Mock(reader) => reader.clone(),
+    };
+    reader.open()?;
+    Ok(reader)
+}
+
+/// Cut a comment off a configuration line,
+/// `#` inside a quoted value is kept
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {},
+        }
+    }
+    line
+}
+
+fn configuration_error(problem: &str) -> UemError {
+    UemError::IncorrectConfiguration(problem.to_string())
+}
@@ -1,17 +1,114 @@
 //! COM port reader interface (RS232/485)
-//! Not implemented!
+//! 
+//! Readers on an RS485 multi-drop line are
+//! addressed through [`UemComBus`](UemComBus).
 
-#![allow(dead_code)]
-
-use std::{time::Duration};
+use std::sync::{Arc, Mutex};
+use std::ops::RangeInclusive;
+use std::io::Write;
+use serialport::{SerialPort, ClearBuffer, DataBits, StopBits, Parity};
 
 use crate::reader::*;
 use crate::reader::processing::*;
 
+/// Default baudrate of MicroEM readers on serial lines
+pub const UEM_COM_BAUDRATE: u32 = 115200;
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+/// Parity checking mode of a serial line
+pub enum UemComParity {
+    /// No parity bit
+    #[default]
+    None,
+    /// Odd parity bit
+    Odd,
+    /// Even parity bit
+    Even,
+}
+
+impl From<UemComParity> for Parity {
+    fn from(parity: UemComParity) -> Self {
+        match parity {
+            UemComParity::None => Parity::None,
+            UemComParity::Odd => Parity::Odd,
+            UemComParity::Even => Parity::Even,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Serial line parameters
+pub struct UemComParameters {
+    /// Line speed in bits per second
+    pub baudrate: u32,
+    /// Line [parity](UemComParity) mode
+    pub parity: UemComParity,
+}
+
+impl Default for UemComParameters {
+    fn default() -> UemComParameters {
+        UemComParameters {
+            baudrate: UEM_COM_BAUDRATE,
+            parity: UemComParity::None,
+        }
+    }
+}
+
+/// Time to wait for each device answer during a bus scan
+const SCAN_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Physical serial line which can be shared
+/// by several readers on a multi-drop bus
 #[derive(Debug, Default)]
+struct ComLine {
+    path: String,
+    parameters: UemComParameters,
+    port: Option<Box<dyn SerialPort>>,
+    users: usize,
+}
+
+type SharedComLine = Arc<Mutex<ComLine>>;
+
+impl ComLine {
+    fn new(path: &str, parameters: &UemComParameters) -> SharedComLine {
+        Arc::new(Mutex::new(ComLine {
+            path: path.to_string(),
+            parameters: *parameters,
+            ..Default::default()
+        }))
+    }
+
+    fn attach(&mut self) -> UemResult {
+        if self.port.is_none() {
+            let port = serialport::new(self.path.as_str(), self.parameters.baudrate)
+                .data_bits(DataBits::Eight)
+                .stop_bits(StopBits::One)
+                .parity(self.parameters.parity.into())
+                .timeout(TIMEOUT)
+                .open()
+                .map_err(|_| UemError::ReaderConnectionFailed)?;
+            self.port = Some(port);
+        }
+        self.users += 1;
+        Ok(())
+    }
+
+    fn detach(&mut self) {
+        self.users = self.users.saturating_sub(1);
+        if self.users == 0 {
+            self.port = None;
+        }
+    }
+}
+
+#[derive(Debug)]
 struct ReaderRs {
+    line: SharedComLine,
+    address: u8,
+    connected: bool,
     timeout: Duration,
-    port: u8,
+    frame: Vec<u8>,
+    assembler: FrameAssembler,
     ncommand: u8,
 }
 
@@ -26,29 +123,280 @@ impl CommandsCounter for ReaderRs {
         }
         self.ncommand += 1;
     }
+
+    fn device_address(&self) -> u8 {
+        self.address
+    }
+}
+
+impl ReaderRs {
+    fn new(line: &SharedComLine, address: u8) -> Self {
+        ReaderRs {
+            line: line.clone(),
+            address,
+            connected: false,
+            timeout: TIMEOUT,
+            frame: Vec::new(),
+            assembler: FrameAssembler::default(),
+            ncommand: initial_commands_count(),
+        }
+    }
 }
 
 impl ReaderRs {
+    /// Send a command frame and collect the response
+    /// frame, reusing buffers of the reader object
+    fn transact(&mut self, command: &[u8]) -> UemResult {
+        if !self.connected {
+            return Err(UemError::ReaderNotConnected);
+        }
+        if command.is_empty() {
+            return Err(UemError::IncorrectParameter);
+        }
+
+        let counter = self.commands_count();
+        let mut send_buffer = std::mem::take(&mut self.frame);
+        prepare_command(self, command, &mut send_buffer);
+
+        // The line stays locked for the whole transaction,
+        // so readers sharing a bus never interleave frames
+        let line = self.line.clone();
+        let mut line = line.lock().unwrap_or_else(|e| e.into_inner());
+        let port = line.port.as_mut().ok_or(UemError::ReaderNotConnected)?;
+        // Readers on the same line may use different timeouts
+        port.set_timeout(self.timeout).map_err(|_| UemError::NotTransacted)?;
+
+        // Drop leftovers of previously timed out responses
+        port.clear(ClearBuffer::Input).map_err(|_| UemError::NotTransacted)?;
+
+        let written = port.write_all(send_buffer.as_slice());
+        self.frame = send_buffer;
+        if written.is_err() {
+            return Err(UemError::NotTransacted);
+        }
+
+        // Frames sent by other devices on the bus are skipped
+        receive_response(port, &mut self.assembler, self.timeout, self.address, counter)
+    }
+}
+
+impl Drop for ReaderRs {
+    fn drop(&mut self) {
+        // Other readers on the bus keep the line open
+        if self.connected {
+            self.line.lock().unwrap_or_else(|e| e.into_inner()).detach();
+        }
+    }
+}
+
+impl UemReaderInternalTrait for ReaderRs {
     /// Open COM interface
-    //#![warn(missing_docs)]
-    pub fn open(&mut self) -> UemResult {
+    fn open(&mut self) -> UemResult {
+        if self.connected {
+            return Err(UemError::ReaderAlreadyConnected);
+        }
+        self.line.lock().unwrap_or_else(|e| e.into_inner()).attach()?;
+        self.connected = true;
         Ok(())
-    }        
+    }
 
-    /// close opened COM interface
-    pub fn close(&mut self) -> core::result::Result<(), UemError> {
+    /// Close opened COM interface
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        if !self.connected {
+            return Err(UemError::ReaderNotConnected);
+        }
+        self.line.lock().unwrap_or_else(|e| e.into_inner()).detach();
+        self.connected = false;
         Ok(())
     }
 
-    #[allow(unused_variables)]
     /// Send command to a reader and receive response
-    pub fn send(&mut self, command: &Vec<u8>) -> UemResultVec {
-        Ok(Vec::new())
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        self.transact(command)?;
+        parse_response(command, self.assembler.frame_mut()).map(|r| r.to_vec())
+    }
+
+    /// Send command to a reader and receive response
+    /// without allocating memory
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        self.transact(command)?;
+        copy_response(parse_response(command, self.assembler.frame_mut())?, response)
+    }
+
+    /// Set response timeout
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        if timeout.is_zero() {
+            return Err(UemError::IncorrectParameter);
+        }
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    /// Current response timeout
+    fn timeout(&self) -> Duration {
+        self.timeout
     }
+
+    /// Set counter of the next command
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        self.ncommand = count;
+        Ok(())
+    }
+}
+
+/// RS485 line shared by several readers
+/// with distinct device addresses
+///
+/// Every reader created from the bus uses the same
+/// serial port, which is opened with the first reader
+/// and closed with the last one. Transactions of
+/// different readers are serialized on the line.
+///
+/// # Example
+///
+/// ```no_run
+/// # use uem_reader::reader::UemReaderInternalTrait;
+/// # use uem_reader::reader::com::*;
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// let uem_bus = UemComBus::new("/dev/ttyUSB0", &UemComParameters::default());
+///
+/// // Look for readers with addresses 1 to 16
+/// let mut uem_readers = uem_bus.scan(1..=16);
+///
+/// for (address, uem_reader) in uem_readers.iter_mut() {
+///     if uem_reader.open().is_err() {
+///         continue;
+///     }
+///     println!("Reader {}: {:?}", address, uem_reader.commands().reader().get_serial());
+///     # uem_reader.close().ok();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct UemComBus {
+    line: SharedComLine,
+}
+
+impl UemComBus {
+    /// Create a bus object for a specific serial port
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Serial port name, e.g. `/dev/ttyUSB0` or `COM3`
+    /// * `parameters` - A reference to a set of [serial line parameters](UemComParameters)
+    pub fn new(path: &str, parameters: &UemComParameters) -> Self {
+        UemComBus { line: ComLine::new(path, parameters) }
+    }
+
+    /// Create a reader object for a device
+    /// with specific address on the bus
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Device address on the bus
+    pub fn reader(&self, address: u8) -> UemReader {
+        Arc::new(Mutex::new(ReaderRs::new(&self.line, address)))
+    }
+
+    /// Probe a range of addresses on the bus
+    ///
+    /// Each address is asked for a reader serial number.
+    ///
+    /// # Arguments
+    ///
+    /// * `addresses` - A range of device addresses to probe
+    ///
+    /// # Returns
+    ///
+    /// Vector of addresses paired with closed reader objects
+    /// for devices which responded correctly.
+    pub fn scan(&self, addresses: RangeInclusive<u8>) -> Vec<(u8, UemReader)> {
+        let mut rs_readers: Vec<(u8, UemReader)> = Vec::new();
+        if self.line.lock().unwrap_or_else(|e| e.into_inner()).attach().is_err() {
+            return rs_readers;
+        }
+        for address in addresses {
+            let mut uem_reader = self.reader(address);
+            if uem_reader.open().is_err() {
+                continue;
+            }
+            let probe = uem_reader.send_with_timeout(&[0x22], SCAN_TIMEOUT);
+            if uem_reader.close().is_err() || probe.is_err() {
+                continue;
+            }
+            rs_readers.push((address, uem_reader));
+        }
+        self.line.lock().unwrap_or_else(|e| e.into_inner()).detach();
+        rs_readers
+    }
+}
+
+/// Create a reader object for a specific serial port
+///
+/// The port is not opened until [`open`](UemReaderInternalTrait::open)
+/// is called on the returned reader.
+///
+/// # Arguments
+///
+/// * `path` - Serial port name, e.g. `/dev/ttyUSB0` or `COM3`
+/// * `parameters` - A reference to a set of [serial line parameters](UemComParameters)
+///
+/// # Example
+///
+/// ```no_run
+/// # use uem_reader::reader::UemReaderInternalTrait;
+/// # use uem_reader::reader::com::*;
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// let mut uem_reader = new_rs_reader("/dev/ttyUSB0", &UemComParameters {
+///     baudrate: 9600,
+///     ..Default::default()
+/// });
+///
+/// if uem_reader.open().is_err() {
+///     return;
+/// }
+///
+/// if uem_reader.commands().reader().beep(1).is_err() {
+///     return;
+/// }
+/// ```
+pub fn new_rs_reader(path: &str, parameters: &UemComParameters) -> UemReader {
+    Arc::new(Mutex::new(ReaderRs::new(&ComLine::new(path, parameters), 0x00)))
 }
 
 /// Searches system for MicroEM readers
 /// on COM ports
+///
+/// Every available serial port is opened with
+/// default [parameters](UemComParameters) and probed
+/// with a version request. Ports which respond correctly
+/// are returned as closed reader objects.
+///
+/// # Example
+///
+/// ```no_run
+/// # use uem_reader::reader::com::find_rs_readers;
+/// let mut uem_readers = find_rs_readers();
+///
+/// if uem_readers.is_empty() {
+///     return;
+/// }
+/// ```
 pub fn find_rs_readers() -> Vec<UemReader> {
-    Vec::new()
-}
\ No newline at end of file
+    let mut rs_readers: Vec<UemReader> = Vec::new();
+    let ports = match serialport::available_ports() {
+        Ok(p) => p,
+        Err(_) => return rs_readers,
+    };
+    for port in ports {
+        let mut uem_reader = new_rs_reader(&port.port_name, &UemComParameters::default());
+        if uem_reader.open().is_err() {
+            continue;
+        }
+        let probe = uem_reader.send(&[0x64]);
+        if uem_reader.close().is_err() || probe.is_err() {
+            continue;
+        }
+        rs_readers.push(uem_reader);
+    }
+    rs_readers
+}
@@ -0,0 +1,390 @@
+//! Fault injection for resilience testing
+//!
+//! An injector wraps any reader object and spoils
+//! some of its transactions the way a flaky radio field
+//! or a flaky link does. Faults are either scripted
+//! or drawn from a seeded random generator, so a failing
+//! run can be reproduced with the same seed.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::reader::*;
+use crate::reader::layer::*;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+/// Kind of injected failure
+pub enum UemFault {
+    /// The command reaches the reader,
+    /// but its response is lost
+    DroppedResponse,
+    /// The response is damaged on the way back and fails
+    /// the check with [`UemInternalError::Crc`](UemInternalError::Crc)
+    Crc,
+    /// The response arrives after the host has given up waiting
+    /// and the command fails with [`UemError::ReaderTimeout`](UemError::ReaderTimeout)
+    /// once the response timeout of the wrapped reader is over
+    Delay,
+    /// The card does not answer, the reader reports
+    /// [`UemInternalError::NoTag`](UemInternalError::NoTag)
+    /// without executing the command
+    NoTag,
+    /// Only a part of the response frame arrives and fails
+    /// the check with [`UemInternalError::Protocol`](UemInternalError::Protocol)
+    TruncatedFrame,
+    /// The response arrives twice, the stale duplicate is dropped
+    /// by the transport for its command counter, so commands
+    /// are not affected
+    DuplicatedFrame,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Fault injection parameters
+///
+/// Probabilities are checked for every command in the
+/// order of fields, the first hit selects the fault.
+pub struct UemFaultParameters {
+    /// Random generator seed
+    pub seed: u64,
+    /// Probability of [dropped response](UemFault::DroppedResponse)
+    pub dropped_response: f64,
+    /// Probability of [CRC error](UemFault::Crc)
+    pub crc: f64,
+    /// Probability of [late response](UemFault::Delay)
+    pub delay: f64,
+    /// Probability of [missing card](UemFault::NoTag)
+    pub no_tag: f64,
+    /// Probability of [truncated response](UemFault::TruncatedFrame)
+    pub truncated_frame: f64,
+    /// Probability of [duplicated response](UemFault::DuplicatedFrame)
+    pub duplicated_frame: f64,
+    /// Minimal time to stall a command with [late response](UemFault::Delay),
+    /// the command stalls at least for the response timeout
+    /// of the wrapped reader
+    pub delay_time: Duration,
+}
+
+impl Default for UemFaultParameters {
+    fn default() -> UemFaultParameters {
+        UemFaultParameters {
+            seed: 0,
+            dropped_response: 0.0,
+            crc: 0.0,
+            delay: 0.0,
+            no_tag: 0.0,
+            truncated_frame: 0.0,
+            duplicated_frame: 0.0,
+            delay_time: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+/// Numbers of commands passed through an injector
+/// and faults injected into them
+pub struct UemFaultCounters {
+    /// Commands sent through the injector
+    pub commands: u64,
+    /// Injected [dropped responses](UemFault::DroppedResponse)
+    pub dropped_responses: u64,
+    /// Injected [CRC errors](UemFault::Crc)
+    pub crc_errors: u64,
+    /// Injected [late responses](UemFault::Delay)
+    pub delays: u64,
+    /// Injected [missing cards](UemFault::NoTag)
+    pub no_tags: u64,
+    /// Injected [truncated responses](UemFault::TruncatedFrame)
+    pub truncated_frames: u64,
+    /// Injected [duplicated responses](UemFault::DuplicatedFrame)
+    pub duplicated_frames: u64,
+}
+
+impl UemFaultCounters {
+    fn count(&mut self, fault: UemFault) {
+        let counter = match fault {
+            UemFault::DroppedResponse => &mut self.dropped_responses,
+            UemFault::Crc => &mut self.crc_errors,
+            UemFault::Delay => &mut self.delays,
+            UemFault::NoTag => &mut self.no_tags,
+            UemFault::TruncatedFrame => &mut self.truncated_frames,
+            UemFault::DuplicatedFrame => &mut self.duplicated_frames,
+        };
+        *counter += 1;
+    }
+}
+
+struct FaultState {
+    parameters: UemFaultParameters,
+    rng: StdRng,
+    scripted: VecDeque<UemFault>,
+    counters: UemFaultCounters,
+}
+
+impl FaultState {
+    fn next_fault(&mut self) -> Option<UemFault> {
+        if let Some(fault) = self.scripted.pop_front() {
+            return Some(fault);
+        }
+        let p = &self.parameters;
+        [
+            (p.dropped_response, UemFault::DroppedResponse),
+            (p.crc, UemFault::Crc),
+            (p.delay, UemFault::Delay),
+            (p.no_tag, UemFault::NoTag),
+            (p.truncated_frame, UemFault::TruncatedFrame),
+            (p.duplicated_frame, UemFault::DuplicatedFrame),
+        ].into_iter()
+            .find(|(probability, _)| *probability > 0.0 && self.rng.gen_bool(probability.min(1.0)))
+            .map(|(_, fault)| fault)
+    }
+}
+
+struct ReaderFault {
+    reader: UemReader,
+    state: Arc<Mutex<FaultState>>,
+}
+
+impl UemReaderInternalTrait for ReaderFault {
+    /// Open wrapped reader
+    fn open(&mut self) -> UemResult {
+        self.reader.open()
+    }
+
+    /// Close wrapped reader
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        self.reader.close()
+    }
+
+    /// Set response timeout of the wrapped reader
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        self.reader.set_timeout(timeout)
+    }
+
+    /// Response timeout of the wrapped reader
+    fn timeout(&self) -> Duration {
+        self.reader.timeout()
+    }
+
+    /// Set command counter of the wrapped reader
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        self.reader.set_commands_count(count)
+    }
+
+    /// Send command to the wrapped reader
+    /// and spoil the transaction if needed
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        self.inject(None, |reader| reader.send(command))
+    }
+
+    /// Send command into a caller-supplied buffer
+    /// and spoil the transaction if needed
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        self.inject(None, |reader| reader.send_into(command, response))
+    }
+
+    /// Send command with a specific response timeout
+    /// and spoil the transaction if needed
+    fn send_with_timeout(&mut self, command: &[u8], timeout: Duration) -> UemResultVec {
+        self.inject(Some(timeout), |reader| reader.send_with_timeout(command, timeout))
+    }
+}
+
+impl ReaderFault {
+    /// Run a `transaction` with a response `timeout`,
+    /// default one if not set, spoiling it if needed
+    fn inject<T>(&mut self, timeout: Option<Duration>, transaction: impl FnOnce(&mut UemReader) -> UemGeneralResult<T>) -> UemGeneralResult<T> {
+        // Readers sharing the layer are not serialized
+        // by the transaction or the stall
+        let (fault, delay_time) = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.counters.commands += 1;
+            let fault = state.next_fault();
+            if let Some(fault) = fault {
+                state.counters.count(fault);
+            }
+            (fault, state.parameters.delay_time)
+        };
+
+        if fault == Some(UemFault::NoTag) {
+            return Err(UemError::ReaderUnsuccessful(UemInternalError::NoTag, None));
+        }
+
+        let res = transaction(&mut self.reader);
+
+        match fault {
+            Some(UemFault::DroppedResponse) => Err(UemError::ReaderResponseFailure),
+            Some(UemFault::Crc) => Err(UemError::ReaderUnsuccessful(UemInternalError::Crc, None)),
+            Some(UemFault::Delay) => {
+                let timeout = timeout.unwrap_or_else(|| self.reader.timeout());
+                std::thread::sleep(timeout.max(delay_time));
+                Err(UemError::ReaderTimeout)
+            },
+            Some(UemFault::TruncatedFrame) => Err(UemError::ReaderUnsuccessful(UemInternalError::Protocol, None)),
+            _ => res,
+        }
+    }
+}
+
+/// Fault injecting layer
+///
+/// Scripted faults are applied first, one per command,
+/// then faults are drawn at random with probabilities
+/// from [parameters](UemFaultParameters). Readers wrapped
+/// by the same layer share faults and counters.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{UemReaderInternalTrait, mock::*, fault::*, layer::*};
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// # let uem_mock = UemReaderMock::new();
+/// # let uem_reader = uem_mock.reader();
+/// let uem_faults = UemFaultLayer::new(&UemFaultParameters {
+///     no_tag: 0.5,
+///     ..Default::default()
+/// });
+///
+/// let mut uem_reader = UemLayers::new()
+///     .layer(uem_faults.clone())
+///     .wrap(uem_reader);
+///
+/// uem_faults.inject(UemFault::NoTag);
+/// assert!(uem_reader.commands().reader().get_serial().is_err());
+/// assert_eq!(uem_faults.counters().no_tags, 1);
+/// # uem_mock.assert_done();
+/// ```
+#[derive(Clone)]
+pub struct UemFaultLayer {
+    state: Arc<Mutex<FaultState>>,
+}
+
+impl UemFaultLayer {
+    /// Create a layer with specific [injection parameters](UemFaultParameters)
+    pub fn new(parameters: &UemFaultParameters) -> Self {
+        UemFaultLayer {
+            state: Arc::new(Mutex::new(FaultState {
+                parameters: *parameters,
+                rng: StdRng::seed_from_u64(parameters.seed),
+                scripted: VecDeque::new(),
+                counters: UemFaultCounters::default(),
+            })),
+        }
+    }
+
+    /// Inject a specific fault into the next command
+    /// not yet covered by scripted faults
+    pub fn inject(&self, fault: UemFault) -> &Self {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).scripted.push_back(fault);
+        self
+    }
+
+    /// Change injection [parameters](UemFaultParameters)
+    /// and reseed the random generator
+    pub fn set_parameters(&self, parameters: &UemFaultParameters) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.parameters = *parameters;
+        state.rng = StdRng::seed_from_u64(parameters.seed);
+    }
+
+    /// Current [counters](UemFaultCounters) of injected faults
+    pub fn counters(&self) -> UemFaultCounters {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).counters
+    }
+
+    /// Reset [counters](UemFaultCounters) of injected faults
+    pub fn reset_counters(&self) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).counters = UemFaultCounters::default();
+    }
+}
+
+impl UemLayer for UemFaultLayer {
+    fn layer(&self, reader: UemReader) -> UemReader {
+        Arc::new(Mutex::new(ReaderFault {
+            reader,
+            state: self.state.clone(),
+        }))
+    }
+}
+
+/// Fault injecting wrapper around a single reader
+///
+/// A shortcut for a [fault injecting layer](UemFaultLayer)
+/// bound to a reader object.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{UemReaderInternalTrait, mock::*, fault::*};
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+/// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+/// # let uem_reader = uem_mock.reader();
+/// let uem_injector = UemFaultInjector::new(uem_reader, &UemFaultParameters {
+///     seed: 42,
+///     ..Default::default()
+/// });
+/// uem_injector.inject(UemFault::Crc);
+///
+/// let mut uem_reader = uem_injector.reader();
+///
+/// assert!(uem_reader.commands().reader().get_serial().is_err());
+/// assert!(uem_reader.commands().reader().get_serial().is_ok());
+///
+/// let counters = uem_injector.counters();
+/// assert_eq!(counters.commands, 2);
+/// assert_eq!(counters.crc_errors, 1);
+/// # uem_mock.assert_done();
+/// ```
+#[derive(Clone)]
+pub struct UemFaultInjector {
+    reader: UemReader,
+    layer: UemFaultLayer,
+}
+
+impl UemFaultInjector {
+    /// Wrap a reader object
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A reader to inject faults into
+    /// * `parameters` - A reference to a set of [injection parameters](UemFaultParameters)
+    pub fn new(reader: UemReader, parameters: &UemFaultParameters) -> Self {
+        UemFaultInjector {
+            reader,
+            layer: UemFaultLayer::new(parameters),
+        }
+    }
+
+    /// Inject a specific fault into the next command
+    /// not yet covered by scripted faults
+    pub fn inject(&self, fault: UemFault) -> &Self {
+        self.layer.inject(fault);
+        self
+    }
+
+    /// Change injection [parameters](UemFaultParameters)
+    /// and reseed the random generator
+    pub fn set_parameters(&self, parameters: &UemFaultParameters) {
+        self.layer.set_parameters(parameters)
+    }
+
+    /// Current [counters](UemFaultCounters) of injected faults
+    pub fn counters(&self) -> UemFaultCounters {
+        self.layer.counters()
+    }
+
+    /// Reset [counters](UemFaultCounters) of injected faults
+    pub fn reset_counters(&self) {
+        self.layer.reset_counters()
+    }
+
+    /// Create a reader object sending commands
+    /// through the injector
+    pub fn reader(&self) -> UemReader {
+        self.layer.layer(self.reader.clone())
+    }
+}
@@ -0,0 +1,286 @@
+//! Background monitoring of reader health
+//!
+//! A monitor sends a version request to a reader idle
+//! for a monitoring interval and tracks failures and latency.
+
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, TryLockError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::reader::*;
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+/// Overall reader condition
+pub enum UemHealthState {
+    /// Reader responds in time
+    #[default]
+    Healthy,
+    /// Reader responds slowly or
+    /// some requests have failed
+    Degraded,
+    /// Reader stopped responding
+    Lost,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Reader health snapshot
+pub struct UemHealthStatus {
+    /// Current [state](UemHealthState)
+    pub state: UemHealthState,
+    /// Number of failed requests in a row
+    pub consecutive_failures: u32,
+    /// Round-trip time of the last successful request
+    pub latency: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Health monitoring parameters
+pub struct UemHealthParameters {
+    /// Time between keep-alive requests
+    pub interval: Duration,
+    /// Number of failures in a row to consider
+    /// the reader degraded
+    pub degraded_after: u32,
+    /// Number of failures in a row to consider
+    /// the reader lost
+    pub lost_after: u32,
+    /// Round-trip time above which the reader
+    /// is considered degraded
+    pub degraded_latency: Duration,
+}
+
+impl Default for UemHealthParameters {
+    fn default() -> UemHealthParameters {
+        UemHealthParameters {
+            interval: Duration::from_secs(5),
+            degraded_after: 1,
+            lost_after: 3,
+            degraded_latency: Duration::from_millis(500),
+        }
+    }
+}
+
+impl UemHealthStatus {
+    fn update(&mut self, result: Option<Duration>, parameters: &UemHealthParameters) {
+        match result {
+            Some(latency) => {
+                self.consecutive_failures = 0;
+                self.latency = Some(latency);
+            },
+            None => self.consecutive_failures = self.consecutive_failures.saturating_add(1),
+        }
+        self.state = if self.consecutive_failures >= parameters.lost_after {
+            UemHealthState::Lost
+        } else if self.consecutive_failures >= parameters.degraded_after ||
+            self.latency.is_some_and(|l| l > parameters.degraded_latency) {
+            UemHealthState::Degraded
+        } else {
+            UemHealthState::Healthy
+        };
+    }
+}
+
+/// Time of the last command sent to a monitored reader
+type Activity = Arc<Mutex<Instant>>;
+
+fn last_activity(activity: &Activity) -> Instant {
+    *activity.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn mark_activity(activity: &Activity) {
+    *activity.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+}
+
+struct ReaderActivity {
+    reader: UemReader,
+    activity: Activity,
+}
+
+impl UemReaderInternalTrait for ReaderActivity {
+    /// Open wrapped reader
+    fn open(&mut self) -> UemResult {
+        self.reader.open()
+    }
+
+    /// Close wrapped reader
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        self.reader.close()
+    }
+
+    /// Set response timeout of the wrapped reader
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        self.reader.set_timeout(timeout)
+    }
+
+    /// Response timeout of the wrapped reader
+    fn timeout(&self) -> Duration {
+        self.reader.timeout()
+    }
+
+    /// Set command counter of the wrapped reader
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        self.reader.set_commands_count(count)
+    }
+
+    /// Send command to the wrapped reader
+    /// and remember the time it completed
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        let res = self.reader.send(command);
+        mark_activity(&self.activity);
+        res
+    }
+
+    /// Send command into a caller-supplied buffer
+    /// and remember the time it completed
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        let res = self.reader.send_into(command, response);
+        mark_activity(&self.activity);
+        res
+    }
+
+    /// Send command with a specific response timeout
+    /// and remember the time it completed
+    fn send_with_timeout(&mut self, command: &[u8], timeout: Duration) -> UemResultVec {
+        let res = self.reader.send_with_timeout(command, timeout);
+        mark_activity(&self.activity);
+        res
+    }
+}
+
+/// Keep-alive monitor attached to a reader
+///
+/// The keep-alive request is only sent when no other
+/// command holds the reader, so it never interleaves
+/// with application commands. Commands sent through a
+/// [tracked](UemHealthMonitor::track) reader object postpone
+/// the request until the reader is idle for the monitoring
+/// interval. Monitoring stops when the monitor or
+/// the reader is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// # use uem_reader::reader::{UemReaderInternalTrait, usb::find_usb_readers};
+/// # use uem_reader::reader::health::*;
+/// # let mut uem_readers = find_usb_readers();
+/// # if uem_readers.is_empty() { return; }
+/// # let uem_reader = uem_readers.get_mut(0);
+/// # if uem_reader.is_none() { return; }
+/// # let uem_reader = uem_reader.unwrap();
+/// # if uem_reader.open().is_err() { return; }
+/// let uem_monitor = UemHealthMonitor::start(uem_reader, &UemHealthParameters::default(),
+///     |status| println!("Reader health changed: {:?}", status.state));
+/// let uem_reader = uem_monitor.track(uem_reader);
+///
+/// // ... use the tracked reader as usual ...
+///
+/// if uem_monitor.status().state == UemHealthState::Lost {
+///     return;
+/// }
+/// ```
+pub struct UemHealthMonitor {
+    status: Arc<Mutex<UemHealthStatus>>,
+    activity: Activity,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UemHealthMonitor {
+    /// Start monitoring an opened reader
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A reader to monitor
+    /// * `parameters` - A reference to a set of [monitoring parameters](UemHealthParameters)
+    /// * `on_change` - Callback receiving [status](UemHealthStatus) on every state change
+    pub fn start(reader: &UemReader, parameters: &UemHealthParameters, mut on_change: impl FnMut(UemHealthStatus) + Send + 'static) -> Self {
+        let status = Arc::new(Mutex::new(UemHealthStatus::default()));
+        let (stop, stopped) = channel::<()>();
+        let reader = Arc::downgrade(reader);
+        let parameters = *parameters;
+        let activity: Activity = Arc::new(Mutex::new(Instant::now()));
+        let thread_status = status.clone();
+        let thread_activity = activity.clone();
+
+        let thread = std::thread::spawn(move || {
+            let mut wait = parameters.interval;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(wait) {
+                let idle = last_activity(&thread_activity).elapsed();
+                if idle < parameters.interval {
+                    // Reader has been used since the last check
+                    wait = parameters.interval - idle;
+                    continue;
+                }
+                wait = parameters.interval;
+                let reader = match reader.upgrade() {
+                    Some(r) => r,
+                    None => break,
+                };
+                let result = match reader.try_lock() {
+                    Ok(mut raw_reader) => {
+                        let started = Instant::now();
+                        let result = raw_reader.send(&[0x64]).ok().map(|_| started.elapsed());
+                        mark_activity(&thread_activity);
+                        result
+                    },
+                    // Reader is busy with an application command
+                    Err(TryLockError::WouldBlock) => continue,
+                    Err(TryLockError::Poisoned(_)) => None,
+                };
+                let (previous, current) = match thread_status.lock() {
+                    Ok(mut status) => {
+                        let previous = status.state;
+                        status.update(result, &parameters);
+                        (previous, *status)
+                    },
+                    Err(_) => break,
+                };
+                if current.state != previous {
+                    on_change(current);
+                }
+            }
+        });
+
+        UemHealthMonitor {
+            status,
+            activity,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Wrap a `reader` into a reader object postponing
+    /// keep-alive requests after each of its commands
+    ///
+    /// Commands sent to the original reader object
+    /// are not seen by the monitor.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The monitored reader
+    ///
+    /// # Returns
+    ///
+    /// A reader object to send application commands with.
+    pub fn track(&self, reader: &UemReader) -> UemReader {
+        Arc::new(Mutex::new(ReaderActivity {
+            reader: reader.clone(),
+            activity: self.activity.clone(),
+        }))
+    }
+
+    /// Current reader [health status](UemHealthStatus)
+    pub fn status(&self) -> UemHealthStatus {
+        self.status.lock().map(|s| *s).unwrap_or_default()
+    }
+}
+
+impl Drop for UemHealthMonitor {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
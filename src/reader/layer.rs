@@ -0,0 +1,461 @@
+//! Composable reader layers
+//!
+//! A layer wraps a reader object into another reader object,
+//! which can alter or observe its commands. Layers are stacked
+//! with [`UemLayers`](UemLayers) and the result is a regular
+//! [`UemReader`](UemReader), so command groups work on top of it.
+//!
+//! Besides layers defined here, see
+//! [fault injection](crate::reader::fault::UemFaultLayer).
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::reader::*;
+
+/// Reader decorator
+///
+/// Any `Fn(UemReader) -> UemReader` closure is a layer as well.
+pub trait UemLayer {
+    /// Wrap a reader object
+    fn layer(&self, reader: UemReader) -> UemReader;
+}
+
+impl<F> UemLayer for F where F: Fn(UemReader) -> UemReader {
+    fn layer(&self, reader: UemReader) -> UemReader {
+        self(reader)
+    }
+}
+
+/// Stack of reader layers
+///
+/// The first added layer is the outermost one:
+/// it sees commands first and responses last.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{UemReaderInternalTrait, mock::*, layer::*};
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// # use uem_reader::errors::*;
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock
+/// #     .expect_error(&[0x22], UemError::ReaderResponseFailure)
+/// #     .expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+/// # let uem_reader = uem_mock.reader();
+/// let uem_metrics = UemMetricsLayer::new();
+///
+/// let mut uem_reader = UemLayers::new()
+///     .layer(UemLogLayer::new(|line| println!("{}", line)))
+///     .layer(uem_metrics.clone())
+///     .layer(UemRetryLayer::new(&UemRetryPolicy::default()))
+///     .wrap(uem_reader);
+///
+/// assert!(uem_reader.commands().reader().get_serial().is_ok());
+/// assert_eq!(uem_metrics.metrics().commands, 1);
+/// # uem_mock.assert_done();
+/// ```
+#[derive(Default)]
+pub struct UemLayers {
+    layers: Vec<Box<dyn UemLayer>>,
+}
+
+impl UemLayers {
+    /// Create an empty stack
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a layer below already added ones
+    pub fn layer(mut self, layer: impl UemLayer + 'static) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    /// Wrap a reader object into all layers of the stack
+    pub fn wrap(&self, reader: UemReader) -> UemReader {
+        self.layers.iter().rev().fold(reader, |reader, layer| layer.layer(reader))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Rules of repeating failed commands
+///
+/// A command is repeated only after a [transient error](UemRetryPolicy::is_transient).
+/// If the command may have reached the reader, it is repeated
+/// only if it is safe to execute it twice.
+pub struct UemRetryPolicy {
+    /// Number of attempts including the first one
+    pub attempts: u32,
+    /// Pause between attempts
+    pub delay: Duration,
+    /// Check if a command may be executed twice,
+    /// [`is_idempotent`](UemRetryPolicy::is_idempotent) by default
+    pub safe_to_resend: fn(&[u8]) -> bool,
+}
+
+impl Default for UemRetryPolicy {
+    fn default() -> UemRetryPolicy {
+        UemRetryPolicy {
+            attempts: 3,
+            delay: Duration::from_millis(10),
+            safe_to_resend: UemRetryPolicy::is_idempotent,
+        }
+    }
+}
+
+impl UemRetryPolicy {
+    /// Check if executing a command twice has the same
+    /// effect as executing it once
+    ///
+    /// Covers commands with codes:
+    ///
+    /// * `0x22`, `0x64` - reader serial and version
+    /// * `0x04`, `0x10` - radio field off and on
+    /// * `0x75` - card activation
+    /// * `0x14` - Mifare Classic authentication
+    /// * `0x19` - Mifare Classic block reading
+    /// * `0x1A` - Mifare Classic block writing, the same
+    ///   data written twice leaves the same block content
+    ///
+    /// Beeping and blinking again is noticeable, commands changing
+    /// values relatively, e.g. value block decrement, are never repeated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use uem_reader::reader::layer::*;
+    /// assert!(UemRetryPolicy::is_idempotent(&[0x19, 0x05]));
+    /// assert!(!UemRetryPolicy::is_idempotent(&[0x05, 0x01]));
+    /// assert!(!UemRetryPolicy::is_idempotent(&[0x07, 0x02, 0x01, 0x00]));
+    /// ```
+    pub fn is_idempotent(command: &[u8]) -> bool {
+        matches!(command.first(),
+            Some(0x04 | 0x10 | 0x14 | 0x19 | 0x1A | 0x22 | 0x64 | 0x75))
+    }
+
+    /// Check if a failed command should be repeated
+    pub fn should_retry(&self, command: &[u8], error: &UemError) -> bool {
+        match error {
+            // The command has not been delivered
+            UemError::NotTransacted => true,
+            e => UemRetryPolicy::is_transient(e) && (self.safe_to_resend)(command),
+        }
+    }
+
+    /// Check if an error is caused by the transport,
+    /// so the same command can succeed on the next attempt
+    pub fn is_transient(error: &UemError) -> bool {
+        matches!(error,
+            UemError::ReaderResponseFailure |
+            UemError::ReaderTimeout |
+            UemError::ReaderIncorrectResponse |
+            UemError::LostCommunicationData |
+            UemError::NotTransacted |
+            UemError::ReaderUnsuccessful(UemInternalError::Crc | UemInternalError::Crc16 | UemInternalError::Framing, _))
+    }
+}
+
+struct ReaderRetry {
+    reader: UemReader,
+    policy: UemRetryPolicy,
+}
+
+impl ReaderRetry {
+    /// Run a `transaction` of a command repeating it according to the policy
+    fn retry<T>(&mut self, command: &[u8], mut transaction: impl FnMut(&mut UemReader) -> UemGeneralResult<T>) -> UemGeneralResult<T> {
+        let mut attempt = 1;
+        loop {
+            match transaction(&mut self.reader) {
+                Err(e) if attempt < self.policy.attempts && self.policy.should_retry(command, &e) => {
+                    attempt += 1;
+                    std::thread::sleep(self.policy.delay);
+                },
+                res => return res,
+            }
+        }
+    }
+}
+
+impl UemReaderInternalTrait for ReaderRetry {
+    /// Open wrapped reader
+    fn open(&mut self) -> UemResult {
+        self.reader.open()
+    }
+
+    /// Close wrapped reader
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        self.reader.close()
+    }
+
+    /// Set response timeout of the wrapped reader
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        self.reader.set_timeout(timeout)
+    }
+
+    /// Response timeout of the wrapped reader
+    fn timeout(&self) -> Duration {
+        self.reader.timeout()
+    }
+
+    /// Set command counter of the wrapped reader
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        self.reader.set_commands_count(count)
+    }
+
+    /// Send command to the wrapped reader
+    /// repeating it according to the policy
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        self.retry(command, |reader| reader.send(command))
+    }
+
+    /// Send command into a caller-supplied buffer
+    /// repeating it according to the policy
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        self.retry(command, |reader| reader.send_into(command, response))
+    }
+
+    /// Send command with a specific response timeout
+    /// repeating it according to the policy
+    fn send_with_timeout(&mut self, command: &[u8], timeout: Duration) -> UemResultVec {
+        self.retry(command, |reader| reader.send_with_timeout(command, timeout))
+    }
+}
+
+/// Layer repeating failed commands
+/// according to a [retry policy](UemRetryPolicy)
+///
+/// Each attempt is sent with a new command counter,
+/// so a late response to a failed attempt is dropped
+/// by the transport and not taken for the next one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UemRetryLayer {
+    policy: UemRetryPolicy,
+}
+
+impl UemRetryLayer {
+    /// Create a layer with specific [retry policy](UemRetryPolicy)
+    pub fn new(policy: &UemRetryPolicy) -> Self {
+        UemRetryLayer { policy: *policy }
+    }
+}
+
+impl UemLayer for UemRetryLayer {
+    fn layer(&self, reader: UemReader) -> UemReader {
+        Arc::new(Mutex::new(ReaderRetry {
+            reader,
+            policy: self.policy,
+        }))
+    }
+}
+
+type LogSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+struct ReaderLog {
+    reader: UemReader,
+    sink: LogSink,
+}
+
+impl ReaderLog {
+    /// Log a transaction of a command
+    fn log(&self, command: &[u8], res: core::result::Result<&[u8], &UemError>, took: Duration) {
+        match res {
+            Ok(data) => (self.sink)(&format!("{:02X?} -> {:02X?} ({:?})", command, data, took)),
+            Err(e) => (self.sink)(&format!("{:02X?} -> {:?} ({:?})", command, e, took)),
+        }
+    }
+}
+
+impl UemReaderInternalTrait for ReaderLog {
+    /// Open wrapped reader
+    fn open(&mut self) -> UemResult {
+        let res = self.reader.open();
+        (self.sink)(&format!("open: {:?}", res));
+        res
+    }
+
+    /// Close wrapped reader
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        let res = self.reader.close();
+        (self.sink)(&format!("close: {:?}", res));
+        res
+    }
+
+    /// Set response timeout of the wrapped reader
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        self.reader.set_timeout(timeout)
+    }
+
+    /// Response timeout of the wrapped reader
+    fn timeout(&self) -> Duration {
+        self.reader.timeout()
+    }
+
+    /// Set command counter of the wrapped reader
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        self.reader.set_commands_count(count)
+    }
+
+    /// Send command to the wrapped reader
+    /// and log the transaction
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        let started = Instant::now();
+        let res = self.reader.send(command);
+        self.log(command, res.as_deref(), started.elapsed());
+        res
+    }
+
+    /// Send command into a caller-supplied buffer
+    /// and log the transaction
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        let started = Instant::now();
+        let res = self.reader.send_into(command, response);
+        self.log(command, res.as_ref().map(|length| &response[..*length]), started.elapsed());
+        res
+    }
+
+    /// Send command with a specific response timeout
+    /// and log the transaction
+    fn send_with_timeout(&mut self, command: &[u8], timeout: Duration) -> UemResultVec {
+        let started = Instant::now();
+        let res = self.reader.send_with_timeout(command, timeout);
+        self.log(command, res.as_deref(), started.elapsed());
+        res
+    }
+}
+
+/// Layer logging every transaction as a text line
+#[derive(Clone)]
+pub struct UemLogLayer {
+    sink: LogSink,
+}
+
+impl UemLogLayer {
+    /// Create a layer passing log lines to a `sink`
+    pub fn new(sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        UemLogLayer { sink: Arc::new(sink) }
+    }
+}
+
+impl UemLayer for UemLogLayer {
+    fn layer(&self, reader: UemReader) -> UemReader {
+        Arc::new(Mutex::new(ReaderLog {
+            reader,
+            sink: self.sink.clone(),
+        }))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Transaction statistics
+pub struct UemMetrics {
+    /// Commands sent
+    pub commands: u64,
+    /// Commands completed with an error
+    pub failures: u64,
+    /// Total time spent in transactions
+    pub total_time: Duration,
+    /// Longest transaction time
+    pub max_time: Duration,
+}
+
+struct ReaderMetrics {
+    reader: UemReader,
+    metrics: Arc<Mutex<UemMetrics>>,
+}
+
+impl ReaderMetrics {
+    /// Run and account a `transaction`
+    fn account<T>(&mut self, transaction: impl FnOnce(&mut UemReader) -> UemGeneralResult<T>) -> UemGeneralResult<T> {
+        let started = Instant::now();
+        let res = transaction(&mut self.reader);
+        let took = started.elapsed();
+        let mut metrics = self.metrics.lock().unwrap_or_else(|e| e.into_inner());
+        metrics.commands += 1;
+        if res.is_err() {
+            metrics.failures += 1;
+        }
+        metrics.total_time += took;
+        metrics.max_time = metrics.max_time.max(took);
+        res
+    }
+}
+
+impl UemReaderInternalTrait for ReaderMetrics {
+    /// Open wrapped reader
+    fn open(&mut self) -> UemResult {
+        self.reader.open()
+    }
+
+    /// Close wrapped reader
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        self.reader.close()
+    }
+
+    /// Set response timeout of the wrapped reader
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        self.reader.set_timeout(timeout)
+    }
+
+    /// Response timeout of the wrapped reader
+    fn timeout(&self) -> Duration {
+        self.reader.timeout()
+    }
+
+    /// Set command counter of the wrapped reader
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        self.reader.set_commands_count(count)
+    }
+
+    /// Send command to the wrapped reader
+    /// and account the transaction
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        self.account(|reader| reader.send(command))
+    }
+
+    /// Send command into a caller-supplied buffer
+    /// and account the transaction
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        self.account(|reader| reader.send_into(command, response))
+    }
+
+    /// Send command with a specific response timeout
+    /// and account the transaction
+    fn send_with_timeout(&mut self, command: &[u8], timeout: Duration) -> UemResultVec {
+        self.account(|reader| reader.send_with_timeout(command, timeout))
+    }
+}
+
+/// Layer collecting [transaction statistics](UemMetrics)
+///
+/// Readers wrapped by the same layer share statistics.
+#[derive(Clone, Default)]
+pub struct UemMetricsLayer {
+    metrics: Arc<Mutex<UemMetrics>>,
+}
+
+impl UemMetricsLayer {
+    /// Create a layer with empty statistics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current [statistics](UemMetrics)
+    pub fn metrics(&self) -> UemMetrics {
+        *self.metrics.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Reset [statistics](UemMetrics)
+    pub fn reset(&self) {
+        *self.metrics.lock().unwrap_or_else(|e| e.into_inner()) = UemMetrics::default();
+    }
+}
+
+impl UemLayer for UemMetricsLayer {
+    fn layer(&self, reader: UemReader) -> UemReader {
+        Arc::new(Mutex::new(ReaderMetrics {
+            reader,
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
@@ -0,0 +1,161 @@
+//! Scripted reader for unit tests
+//!
+//! The mock checks that commands arrive in the expected
+//! order and answers them with canned responses,
+//! so command groups can be tested without hardware.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::reader::*;
+
+#[derive(Debug, Default)]
+struct MockState {
+    expectations: VecDeque<(Vec<u8>, UemResultVec)>,
+    mismatches: Vec<String>,
+    connected: bool,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct ReaderMock {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl UemReaderInternalTrait for ReaderMock {
+    /// Open mock reader
+    fn open(&mut self) -> UemResult {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.connected {
+            return Err(UemError::ReaderAlreadyConnected);
+        }
+        state.connected = true;
+        Ok(())
+    }
+
+    /// Close mock reader
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if !state.connected {
+            return Err(UemError::ReaderNotConnected);
+        }
+        state.connected = false;
+        Ok(())
+    }
+
+    /// Check a command against the next expectation
+    /// and return its scripted response
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.expectations.pop_front() {
+            Some((expected, response)) if expected == command => response,
+            Some((expected, _)) => {
+                state.mismatches.push(format!("expected command {:02X?}, got {:02X?}", expected, command));
+                Err(UemError::Unexpected)
+            },
+            None => {
+                state.mismatches.push(format!("unexpected command {:02X?}", command));
+                Err(UemError::Unexpected)
+            },
+        }
+    }
+
+    /// Keep response timeout, so it can be checked
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).timeout = Some(timeout);
+        Ok(())
+    }
+
+    /// Current response timeout
+    fn timeout(&self) -> Duration {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).timeout.unwrap_or(TIMEOUT)
+    }
+}
+
+/// Scripted reader
+///
+/// Expectations are consumed in the order they were added.
+/// A command which does not match the next expectation
+/// fails with [`UemError::Unexpected`](UemError::Unexpected)
+/// and is reported by [`assert_done`](UemReaderMock::assert_done).
+///
+/// The mock object stays usable after a reader
+/// object has been created from it.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{UemReaderInternalTrait, mock::*};
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// # use uem_reader::errors::*;
+/// let uem_mock = UemReaderMock::new();
+/// uem_mock
+///     .expect(&[0x05, 0x02], &[])
+///     .expect_error(&[0x22], UemError::ReaderResponseFailure);
+///
+/// let mut uem_reader = uem_mock.reader();
+///
+/// assert!(uem_reader.commands().reader().beep(2).is_ok());
+/// assert!(uem_reader.commands().reader().get_serial().is_err());
+///
+/// uem_mock.assert_done();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct UemReaderMock {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl UemReaderMock {
+    /// Create a mock without expectations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect a command and answer it with response data
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Expected command bytes
+    /// * `response` - Response bytes following the status byte
+    pub fn expect(&self, command: &[u8], response: &[u8]) -> &Self {
+        self.push(command, Ok(response.to_vec()))
+    }
+
+    /// Expect a command and fail it with an error
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Expected command bytes
+    /// * `error` - [Error](UemError) to return
+    pub fn expect_error(&self, command: &[u8], error: UemError) -> &Self {
+        self.push(command, Err(error))
+    }
+
+    /// Create a reader object backed by the mock
+    pub fn reader(&self) -> UemReader {
+        Arc::new(Mutex::new(ReaderMock { state: self.state.clone() }))
+    }
+
+    /// Check that every expectation has been consumed
+    /// and no unexpected command has been received
+    pub fn is_done(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.expectations.is_empty() && state.mismatches.is_empty()
+    }
+
+    /// Panic if some expectations are left or
+    /// unexpected commands have been received
+    pub fn assert_done(&self) {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        assert!(state.mismatches.is_empty(), "mock reader: {}", state.mismatches.join("; "));
+        assert!(state.expectations.is_empty(),
+            "mock reader: {} expectation(s) left, next is {:02X?}",
+            state.expectations.len(), state.expectations[0].0);
+    }
+
+    fn push(&self, command: &[u8], response: UemResultVec) -> &Self {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).expectations.push_back((command.to_vec(), response));
+        self
+    }
+}
@@ -0,0 +1,408 @@
+//! Pools of readers driven together
+//!
+//! A [pool](UemReaderPool) keeps opened readers keyed by the serial
+//! number they report, runs operations on all of them in parallel
+//! or on any free one, and collects [results](UemPoolResults)
+//! of every reader. A reader reporting
+//! [`UemError::ReaderDisconnected`](UemError::ReaderDisconnected)
+//! drops out of the pool and can rejoin it once it is found again.
+//!
+//! # Example
+//!
+//! ```
+//! # use uem_reader::reader::{mock::UemReaderMock, pool::*};
+//! # use uem_reader::commands::{UemCommandsTrait, reader::*};
+//! # let (first_mock, second_mock) = (UemReaderMock::new(), UemReaderMock::new());
+//! # first_mock.expect(&[0x22], &[0x00, 0x00, 0x00, 0x01]).expect(&[0x05, 0x01], &[]);
+//! # second_mock.expect(&[0x22], &[0x00, 0x00, 0x00, 0x02]).expect(&[0x05, 0x01], &[]);
+//! let uem_pool = UemReaderPool::new();
+//! uem_pool.add(first_mock.reader()).unwrap();
+//! uem_pool.add(second_mock.reader()).unwrap();
+//!
+//! let results = uem_pool.run_all(|_serial, session| {
+//!     session.commands().reader().beep(1)
+//! });
+//! assert!(results.is_ok());
+//! assert_eq!(results.serials(), ["00000001", "00000002"]);
+//! # drop(uem_pool);
+//! # first_mock.assert_done();
+//! # second_mock.assert_done();
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use crate::reader::*;
+use crate::reader::usb::hotplug::UemUsbEvent;
+use crate::commands::{reader::*, session::*};
+
+struct PoolEntry {
+    reader: UemReader,
+    busy: bool,
+}
+
+/// Per-reader results of a pool operation, keyed by reader serial
+#[derive(Debug)]
+pub struct UemPoolResults<T> {
+    /// Result of every reader the operation has run on
+    pub results: BTreeMap<String, core::result::Result<T, UemError>>,
+}
+
+impl<T> UemPoolResults<T> {
+    /// Check that the operation succeeded on every reader
+    pub fn is_ok(&self) -> bool {
+        self.results.values().all(|r| r.is_ok())
+    }
+
+    /// Serials of readers the operation has run on
+    pub fn serials(&self) -> Vec<&str> {
+        self.results.keys().map(|s| s.as_str()).collect()
+    }
+
+    /// Results of readers the operation succeeded on
+    pub fn successes(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.results.iter().filter_map(|(s, r)| r.as_ref().ok().map(|v| (s.as_str(), v)))
+    }
+
+    /// Errors of readers the operation failed on
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &UemError)> {
+        self.results.iter().filter_map(|(s, r)| r.as_ref().err().map(|e| (s.as_str(), e)))
+    }
+}
+
+/// Reader taken from a pool for a single operation,
+/// returned to the pool when dropped
+struct Lease<'p> {
+    pool: &'p UemReaderPool,
+    serial: String,
+    reader: UemReader,
+    disconnected: bool,
+}
+
+impl Lease<'_> {
+    fn run<T>(&mut self, operation: impl FnOnce(&str, &mut UemSession<'_>) -> UemGeneralResult<T>) -> UemGeneralResult<T> {
+        let serial = self.serial.as_str();
+        let res = self.reader.with_session(|session| operation(serial, session));
+        self.disconnected = matches!(res, Err(UemError::ReaderDisconnected));
+        res
+    }
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        let mut readers = self.pool.lock();
+        let same_reader = readers.get(&self.serial)
+            .is_some_and(|entry| Arc::ptr_eq(&entry.reader, &self.reader));
+        // A reader the operation has panicked on
+        // is left poisoned in an unknown state
+        let poisoned = std::thread::panicking();
+        let dropped_out = same_reader && (self.disconnected || poisoned);
+        if dropped_out {
+            readers.remove(&self.serial);
+        } else if let Some(entry) = readers.get_mut(&self.serial).filter(|_| same_reader) {
+            entry.busy = false;
+        }
+        drop(readers);
+        self.pool.released.notify_all();
+        if dropped_out && !poisoned {
+            self.reader.close().ok();
+        }
+    }
+}
+
+/// Set of opened readers keyed by their serial numbers
+///
+/// The pool can be shared between threads by reference
+/// or in an `Arc`. A reader runs a single pool operation
+/// at a time, operations on different readers run in parallel.
+/// Readers are closed when the pool is dropped.
+#[derive(Default)]
+pub struct UemReaderPool {
+    readers: Mutex<BTreeMap<String, PoolEntry>>,
+    released: Condvar,
+}
+
+impl UemReaderPool {
+    /// Create an empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a pool of all readers found on USB ports,
+    /// see [`add_usb_readers`](UemReaderPool::add_usb_readers)
+    pub fn from_usb() -> Self {
+        let pool = Self::new();
+        pool.add_usb_readers();
+        pool
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, PoolEntry>> {
+        self.readers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Open a reader and add it to the pool
+    ///
+    /// A reader with the same serial already in the pool,
+    /// e.g. one which has been unplugged and plugged in
+    /// again, is replaced and closed.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(String)` with reader serial in hexadecimal form on success,
+    /// otherwise returns an error of opening or reading the serial.
+    pub fn add(&self, mut reader: UemReader) -> core::result::Result<String, UemError> {
+        match reader.open() {
+            Ok(()) | Err(UemError::ReaderAlreadyConnected) => {},
+            Err(e) => return Err(e),
+        }
+        let serial = match reader.commands().reader().get_serial() {
+            Ok(serial) => serial.iter().map(|b| format!("{:02X}", b)).collect::<String>(),
+            Err(e) => {
+                reader.close().ok();
+                return Err(e);
+            },
+        };
+        let replaced = self.lock().insert(serial.clone(), PoolEntry { reader: reader.clone(), busy: false });
+        self.released.notify_all();
+        if let Some(mut entry) = replaced.filter(|entry| !Arc::ptr_eq(&entry.reader, &reader)) {
+            entry.reader.close().ok();
+        }
+        Ok(serial)
+    }
+
+    /// Open readers found on USB ports and add them to the pool
+    ///
+    /// Readers already in the pool or used by other
+    /// applications cannot be opened and are skipped,
+    /// so the method can be called periodically to let
+    /// dropped out readers rejoin the pool.
+    ///
+    /// # Returns
+    ///
+    /// Serials of readers added to the pool.
+    pub fn add_usb_readers(&self) -> Vec<String> {
+        find_usb_readers().into_iter()
+            .filter_map(|reader| self.add(reader).ok())
+            .collect()
+    }
+
+    /// Update the pool on a [USB reader event](UemUsbEvent)
+    /// of a [watcher](crate::reader::usb::hotplug::UemUsbWatcher)
+    ///
+    /// Arrived readers are added, readers left are removed and closed.
+    ///
+    /// # Returns
+    ///
+    /// Serial of the reader added or removed, if any.
+    pub fn handle_usb_event(&self, event: UemUsbEvent) -> Option<String> {
+        match event {
+            UemUsbEvent::Arrived(reader) => self.add(reader).ok(),
+            UemUsbEvent::Left(reader) => {
+                let mut readers = self.lock();
+                let serial = readers.iter()
+                    .find(|(_, entry)| Arc::ptr_eq(&entry.reader, &reader))
+                    .map(|(serial, _)| serial.clone())?;
+                let entry = readers.remove(&serial);
+                drop(readers);
+                self.released.notify_all();
+                if let Some(mut entry) = entry {
+                    entry.reader.close().ok();
+                }
+                Some(serial)
+            },
+        }
+    }
+
+    /// Take a reader out of the pool
+    ///
+    /// The reader is returned open, an operation
+    /// running on it is not interrupted.
+    pub fn remove(&self, serial: &str) -> Option<UemReader> {
+        let entry = self.lock().remove(serial);
+        self.released.notify_all();
+        entry.map(|e| e.reader)
+    }
+
+    /// Serials of readers in the pool
+    pub fn serials(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    /// Number of readers in the pool
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Check if the pool has no readers
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Wait until a reader chosen by `pick` is free and take it
+    fn lease(&self, pick: impl Fn(&BTreeMap<String, PoolEntry>) -> Option<Option<String>>) -> UemGeneralResult<Lease<'_>> {
+        let mut readers = self.lock();
+        loop {
+            // `None` - no suitable reader, `Some(None)` - all of them are busy
+            let serial = pick(&readers).ok_or(UemError::ReaderNotConnected)?;
+            if let Some(entry) = serial.as_ref().and_then(|s| readers.get_mut(s)) {
+                entry.busy = true;
+                let reader = entry.reader.clone();
+                return Ok(Lease { pool: self, serial: serial.unwrap_or_default(), reader, disconnected: false });
+            }
+            readers = self.released.wait(readers).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Run an `operation` on a specific reader
+    ///
+    /// The operation runs in an [exclusive session](UemSession)
+    /// once the reader is free.
+    ///
+    /// # Returns
+    ///
+    /// Result of the `operation`, or
+    /// [`UemError::ReaderNotConnected`](UemError::ReaderNotConnected)
+    /// if there is no such reader in the pool.
+    /// A reader the operation panics on is removed from the pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use uem_reader::errors::UemError;
+    /// # use uem_reader::reader::{mock::UemReaderMock, pool::*};
+    /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x22], &[0x00, 0x00, 0x00, 0x01]);
+    /// # let uem_pool = UemReaderPool::new();
+    /// # uem_pool.add(uem_mock.reader()).unwrap();
+    /// // A panicking operation fails alone
+    /// let broken = uem_pool.run_all(|_serial, _session| -> Result<(), UemError> {
+    ///     panic!("broken operation")
+    /// });
+    /// assert!(!broken.is_ok());
+    ///
+    /// // And takes its reader out of the pool
+    /// let beep = uem_pool.run("00000001", |_serial, session| {
+    ///     session.commands().reader().beep(1)
+    /// });
+    /// assert!(matches!(beep, Err(UemError::ReaderNotConnected)));
+    /// assert!(uem_pool.is_empty());
+    /// # drop(uem_pool);
+    /// # uem_mock.assert_done();
+    /// ```
+    pub fn run<T>(&self, serial: &str, operation: impl FnOnce(&str, &mut UemSession<'_>) -> UemGeneralResult<T>) -> UemGeneralResult<T> {
+        self.lease(|readers| {
+            let entry = readers.get(serial)?;
+            Some((!entry.busy).then(|| serial.to_string()))
+        })?.run(operation)
+    }
+
+    /// Run an `operation` on any free reader,
+    /// waiting for one if all of them are busy
+    ///
+    /// # Returns
+    ///
+    /// Serial of the reader the operation has run on with its result,
+    /// or [`UemError::ReaderNotConnected`](UemError::ReaderNotConnected)
+    /// if the pool is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use uem_reader::reader::{mock::UemReaderMock, pool::*};
+    /// # use uem_reader::commands::{UemCommandsTrait, cards::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x22], &[0x00, 0x00, 0x00, 0x01]);
+    /// # uem_mock.expect(&[0x75, 0x00, 0xAA, 0x80], &[0x04, 0x00, 0x08, 0x04, 0x01, 0x02, 0x03, 0x04]);
+    /// # let uem_pool = UemReaderPool::new();
+    /// # uem_pool.add(uem_mock.reader()).unwrap();
+    /// let (serial, card) = uem_pool.run_any(|_serial, session| {
+    ///     session.commands().cards().activate_a(&UemActivateParameters::default())
+    /// }).unwrap();
+    /// # assert_eq!(serial, "00000001");
+    /// # assert!(card.is_ok());
+    /// # drop(uem_pool);
+    /// # uem_mock.assert_done();
+    /// ```
+    pub fn run_any<T>(&self, operation: impl FnOnce(&str, &mut UemSession<'_>) -> UemGeneralResult<T>) -> UemGeneralResult<(String, UemGeneralResult<T>)> {
+        let mut lease = self.lease(|readers| {
+            if readers.is_empty() {
+                return None;
+            }
+            Some(readers.iter().find(|(_, entry)| !entry.busy).map(|(serial, _)| serial.clone()))
+        })?;
+        let res = lease.run(operation);
+        Ok((lease.serial.clone(), res))
+    }
+
+    /// Run an `operation` on every reader of the pool in parallel
+    ///
+    /// Every reader runs the operation in an
+    /// [exclusive session](UemSession) on a thread of its own.
+    /// Readers reporting
+    /// [`UemError::ReaderDisconnected`](UemError::ReaderDisconnected)
+    /// are removed from the pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use uem_reader::errors::UemError;
+    /// # use uem_reader::reader::{mock::UemReaderMock, pool::*};
+    /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+    /// # let (first_mock, second_mock) = (UemReaderMock::new(), UemReaderMock::new());
+    /// # first_mock.expect(&[0x22], &[0x00, 0x00, 0x00, 0x01]).expect(&[0x64], &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    /// # second_mock.expect(&[0x22], &[0x00, 0x00, 0x00, 0x02]).expect_error(&[0x64], UemError::ReaderDisconnected);
+    /// # second_mock.expect(&[0x22], &[0x00, 0x00, 0x00, 0x02]);
+    /// # let uem_pool = UemReaderPool::new();
+    /// # uem_pool.add(first_mock.reader()).unwrap();
+    /// # uem_pool.add(second_mock.reader()).unwrap();
+    /// let versions = uem_pool.run_all(|_serial, session| {
+    ///     session.commands().reader().get_version()
+    /// });
+    /// for (serial, error) in versions.errors() {
+    ///     println!("{}: {}", serial, error);
+    /// }
+    /// # assert_eq!(versions.successes().count(), 1);
+    ///
+    /// // The unplugged reader has dropped out
+    /// assert_eq!(uem_pool.serials(), ["00000001"]);
+    /// // Until it is found again
+    /// uem_pool.add(second_mock.reader()).unwrap();
+    /// assert_eq!(uem_pool.len(), 2);
+    /// # drop(uem_pool);
+    /// # first_mock.assert_done();
+    /// # second_mock.assert_done();
+    /// ```
+    pub fn run_all<T, F>(&self, operation: F) -> UemPoolResults<T>
+    where
+        T: Send,
+        F: Fn(&str, &mut UemSession<'_>) -> UemGeneralResult<T> + Sync,
+    {
+        let serials = self.serials();
+        let operation = &operation;
+        let results = std::thread::scope(|scope| {
+            let threads: Vec<_> = serials.into_iter()
+                .map(|serial| {
+                    let thread = scope.spawn({
+                        let serial = serial.clone();
+                        move || self.run(&serial, operation)
+                    });
+                    (serial, thread)
+                })
+                .collect();
+            threads.into_iter()
+                // The operation has panicked
+                .map(|(serial, thread)| (serial, thread.join().unwrap_or(Err(UemError::Unexpected))))
+                .collect()
+        });
+        UemPoolResults { results }
+    }
+}
+
+impl Drop for UemReaderPool {
+    fn drop(&mut self) {
+        for entry in self.lock().values_mut() {
+            entry.reader.close().ok();
+        }
+    }
+}
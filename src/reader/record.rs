@@ -0,0 +1,432 @@
+//! Session recording and replay
+//!
+//! A recording reader passes commands to a wrapped reader
+//! and writes every transaction into a text log. A replay
+//! reader serves responses from such a log, so a session
+//! captured in the field can be re-run in a test.
+//!
+//! The log has one transaction per line: start time and
+//! duration in milliseconds, command bytes in hex and either
+//! `ok` with response bytes or `err` with an error:
+//!
+//! ```text
+//! # uem-reader session
+//! 0.012 3.870 22 ok 01020304
+//! 4.120 1000.210 1905 err ReaderResponseFailure
+//! 1005.004 2.015 1905 err ReaderUnsuccessful:F6
+//! ```
+//!
+//! Error messages are written as hex of their UTF-8 bytes,
+//! e.g. `IncorrectConfiguration:6E6F2070617468`.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::reader::*;
+
+const SESSION_HEADER: &str = "# uem-reader session";
+
+fn to_hex(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "-".to_string();
+    }
+    data.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if text == "-" {
+        return Some(vec![]);
+    }
+    if text.len() % 2 == 1 {
+        return None;
+    }
+    (0..text.len()).step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn error_to_text(error: &UemError) -> String {
+    match error {
+        UemError::ReaderUnsuccessful(code, Some(data)) =>
+            format!("ReaderUnsuccessful:{:02X}:{}", *code as u8, to_hex(data)),
+        UemError::ReaderUnsuccessful(code, None) =>
+            format!("ReaderUnsuccessful:{:02X}", *code as u8),
+        // The message may contain spaces and colons
+        UemError::IncorrectConfiguration(message) =>
+            format!("IncorrectConfiguration:{}", to_hex(message.as_bytes())),
+        e => format!("{:?}", e),
+    }
+}
+
+fn error_from_text(text: &str) -> Option<UemError> {
+    let mut parts = text.split(':');
+    let error = match parts.next()? {
+        "PendingOperation" => UemError::PendingOperation,
+        "UnsupportedFeature" => UemError::UnsupportedFeature,
+        "LostCommunicationData" => UemError::LostCommunicationData,
+        "IncorrectParameter" => UemError::IncorrectParameter,
+        "Unexpected" => UemError::Unexpected,
+        "Access" => UemError::Access,
+        "NotTransacted" => UemError::NotTransacted,
+        "IncorrectReaderName" => UemError::IncorrectReaderName,
+        "ReaderConnectionFailed" => UemError::ReaderConnectionFailed,
+        "ReaderNotConnected" => UemError::ReaderNotConnected,
+        "ReaderDisconnected" => UemError::ReaderDisconnected,
+        "ReaderAlreadyConnected" => UemError::ReaderAlreadyConnected,
+        "ReaderIncorrectResponse" => UemError::ReaderIncorrectResponse,
+        "ReaderResponseFailure" => UemError::ReaderResponseFailure,
+        "ReaderTimeout" => UemError::ReaderTimeout,
+        "ReaderPoisoned" => UemError::ReaderPoisoned,
+        "SamApdu" => UemError::SamApdu,
+        "SamInvalidMac" => UemError::SamInvalidMac,
+        "SamAuthenticationFailed" => UemError::SamAuthenticationFailed,
+        "IncorrectConfiguration" => {
+            let message = from_hex(parts.next()?)?;
+            UemError::IncorrectConfiguration(String::from_utf8(message).ok()?)
+        },
+        "ReaderUnsuccessful" => {
+            let code = u8::from_str_radix(parts.next()?, 16).ok()?;
+            let data = match parts.next() {
+                Some(d) => Some(from_hex(d)?),
+                None => None,
+            };
+            UemError::ReaderUnsuccessful(UemInternalError::from_byte(code), data)
+        },
+        _ => return None,
+    };
+    Some(error)
+}
+
+type SessionLog = Box<dyn Write + Send>;
+
+struct ReaderRecording {
+    reader: UemReader,
+    log: SessionLog,
+    started: Instant,
+}
+
+impl UemReaderInternalTrait for ReaderRecording {
+    /// Open wrapped reader
+    fn open(&mut self) -> UemResult {
+        self.reader.open()
+    }
+
+    /// Close wrapped reader
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        self.log.flush().ok();
+        self.reader.close()
+    }
+
+    /// Set response timeout of the wrapped reader
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        self.reader.set_timeout(timeout)
+    }
+
+    /// Response timeout of the wrapped reader
+    fn timeout(&self) -> Duration {
+        self.reader.timeout()
+    }
+
+    /// Set command counter of the wrapped reader
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        self.reader.set_commands_count(count)
+    }
+
+    /// Send command to the wrapped reader
+    /// and log the transaction
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        let at = self.started.elapsed();
+        let res = self.reader.send(command);
+        self.record(command, at, res.as_deref());
+        res
+    }
+
+    /// Send command into a caller-supplied buffer
+    /// and log the transaction
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        let at = self.started.elapsed();
+        let res = self.reader.send_into(command, response);
+        self.record(command, at, res.as_ref().map(|length| &response[..*length]));
+        res
+    }
+
+    /// Send command with a specific response timeout
+    /// and log the transaction
+    fn send_with_timeout(&mut self, command: &[u8], timeout: Duration) -> UemResultVec {
+        let at = self.started.elapsed();
+        let res = self.reader.send_with_timeout(command, timeout);
+        self.record(command, at, res.as_deref());
+        res
+    }
+}
+
+impl ReaderRecording {
+    /// Write a transaction started `at` into the log
+    fn record(&mut self, command: &[u8], at: Duration, res: core::result::Result<&[u8], &UemError>) {
+        let took = self.started.elapsed() - at;
+        let result = match res {
+            Ok(data) => format!("ok {}", to_hex(data)),
+            Err(e) => format!("err {}", error_to_text(e)),
+        };
+        // Flush every line, so the log survives a crash
+        writeln!(self.log, "{:.3} {:.3} {} {}",
+            at.as_secs_f64() * 1000.0, took.as_secs_f64() * 1000.0, to_hex(command), result)
+            .and_then(|_| self.log.flush()).ok();
+    }
+}
+
+/// Wrap a reader object to record its session into a file
+///
+/// The file is overwritten. Command counters are not logged,
+/// a replay matches commands by their bytes.
+///
+/// # Arguments
+///
+/// * `reader` - A reader to record
+/// * `path` - Path of the log file
+///
+/// # Returns
+///
+/// `Ok(UemReader)` with the recording reader on success,
+/// [`UemError::Access`](UemError::Access) if the file cannot be created.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::reader::{UemReaderInternalTrait, mock::*, record::*};
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// # use uem_reader::errors::*;
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock
+/// #     .expect(&[0x22], &[0x01, 0x02, 0x03, 0x04])
+/// #     .expect_error(&[0x64], UemError::ReaderResponseFailure);
+/// # let uem_reader = uem_mock.reader();
+/// let path = std::env::temp_dir().join("uem-reader-session.log");
+/// let path = path.to_str().unwrap();
+///
+/// // Record a session
+/// let mut uem_reader = new_recording_reader(uem_reader, path).unwrap();
+/// let serial = uem_reader.commands().reader().get_serial();
+/// assert!(uem_reader.commands().reader().get_version().is_err());
+///
+/// // Re-run it without the reader
+/// let uem_replay = UemReplay::load(path).unwrap();
+/// let mut uem_reader = uem_replay.reader();
+/// assert_eq!(uem_reader.commands().reader().get_serial().unwrap(), serial.unwrap());
+/// assert!(uem_reader.commands().reader().get_version().is_err());
+/// uem_replay.assert_done();
+/// # std::fs::remove_file(path).ok();
+/// ```
+pub fn new_recording_reader(reader: UemReader, path: &str) -> core::result::Result<UemReader, UemError> {
+    let file = File::create(path).map_err(|_| UemError::Access)?;
+    new_recording_reader_with(reader, BufWriter::new(file))
+}
+
+/// Wrap a reader object to record its session into a writer
+///
+/// # Arguments
+///
+/// * `reader` - A reader to record
+/// * `log` - Destination of the session log
+///
+/// # Returns
+///
+/// `Ok(UemReader)` with the recording reader on success,
+/// [`UemError::Access`](UemError::Access) if the log header cannot be written.
+pub fn new_recording_reader_with(reader: UemReader, log: impl Write + Send + 'static) -> core::result::Result<UemReader, UemError> {
+    let mut log: SessionLog = Box::new(log);
+    writeln!(log, "{}", SESSION_HEADER).map_err(|_| UemError::Access)?;
+    log.flush().map_err(|_| UemError::Access)?;
+    Ok(Arc::new(Mutex::new(ReaderRecording {
+        reader,
+        log,
+        started: Instant::now(),
+    })))
+}
+
+#[derive(Debug, Clone)]
+struct Transaction {
+    command: Vec<u8>,
+    result: UemResultVec,
+    took: Duration,
+}
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    transactions: VecDeque<Transaction>,
+    mismatches: Vec<String>,
+    connected: bool,
+    realtime: bool,
+    timeout: Option<Duration>,
+}
+
+struct ReaderReplay {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl UemReaderInternalTrait for ReaderReplay {
+    /// Open replay reader
+    fn open(&mut self) -> UemResult {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.connected {
+            return Err(UemError::ReaderAlreadyConnected);
+        }
+        state.connected = true;
+        Ok(())
+    }
+
+    /// Close replay reader
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if !state.connected {
+            return Err(UemError::ReaderNotConnected);
+        }
+        state.connected = false;
+        Ok(())
+    }
+
+    /// Check a command against the next recorded
+    /// transaction and return its recorded result
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let transaction = match state.transactions.pop_front() {
+            Some(t) if t.command == command => t,
+            Some(t) => {
+                state.mismatches.push(format!("recorded command {:02X?}, got {:02X?}", t.command, command));
+                return Err(UemError::Unexpected);
+            },
+            None => {
+                state.mismatches.push(format!("command {:02X?} beyond the end of the session", command));
+                return Err(UemError::Unexpected);
+            },
+        };
+        if state.realtime {
+            std::thread::sleep(transaction.took);
+        }
+        transaction.result
+    }
+
+    /// Keep response timeout, recorded results
+    /// are returned regardless of it
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).timeout = Some(timeout);
+        Ok(())
+    }
+
+    /// Current response timeout
+    fn timeout(&self) -> Duration {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).timeout.unwrap_or(TIMEOUT)
+    }
+}
+
+/// Recorded session served back as a reader
+///
+/// Commands must arrive in the recorded order. A command
+/// which does not match the next recorded one fails with
+/// [`UemError::Unexpected`](UemError::Unexpected)
+/// and is reported by [`assert_done`](UemReplay::assert_done).
+#[derive(Debug, Clone)]
+pub struct UemReplay {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl UemReplay {
+    /// Load a session log written by [`new_recording_reader`](new_recording_reader)
+    ///
+    /// # Returns
+    ///
+    /// `Ok(UemReplay)` on success, [`UemError::Access`](UemError::Access)
+    /// if the file cannot be read, [`UemError::IncorrectParameter`](UemError::IncorrectParameter)
+    /// if the log is malformed.
+    pub fn load(path: &str) -> core::result::Result<Self, UemError> {
+        let log = std::fs::read_to_string(path).map_err(|_| UemError::Access)?;
+        Self::parse(&log)
+    }
+
+    /// Parse a session log
+    ///
+    /// # Returns
+    ///
+    /// `Ok(UemReplay)` on success, [`UemError::IncorrectParameter`](UemError::IncorrectParameter)
+    /// if the log is malformed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use uem_reader::errors::UemError;
+    /// # use uem_reader::reader::{UemReaderInternalTrait, record::*};
+    /// let uem_replay = UemReplay::parse("\
+    ///     0.012 3.870 22 ok 01020304
+    ///     4.120 0.010 64 err IncorrectConfiguration:6E6F3A2070617468
+    /// ").unwrap();
+    /// let mut uem_reader = uem_replay.reader();
+    ///
+    /// assert_eq!(uem_reader.send(&[0x22]).unwrap(), [0x01, 0x02, 0x03, 0x04]);
+    /// assert!(matches!(uem_reader.send(&[0x64]),
+    ///     Err(UemError::IncorrectConfiguration(m)) if m == "no: path"));
+    /// # uem_replay.assert_done();
+    /// ```
+    pub fn parse(log: &str) -> core::result::Result<Self, UemError> {
+        let mut transactions = VecDeque::new();
+        for line in log.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let transaction = Self::parse_transaction(line).ok_or(UemError::IncorrectParameter)?;
+            transactions.push_back(transaction);
+        }
+        Ok(UemReplay {
+            state: Arc::new(Mutex::new(ReplayState {
+                transactions,
+                ..Default::default()
+            })),
+        })
+    }
+
+    fn parse_transaction(line: &str) -> Option<Transaction> {
+        let mut fields = line.split_whitespace();
+        let _at: f64 = fields.next()?.parse().ok()?;
+        let took: f64 = fields.next()?.parse().ok()?;
+        let command = from_hex(fields.next()?)?;
+        let result = match (fields.next()?, fields.next()?) {
+            ("ok", data) => Ok(from_hex(data)?),
+            ("err", error) => Err(error_from_text(error)?),
+            _ => return None,
+        };
+        Some(Transaction {
+            command,
+            result,
+            took: Duration::try_from_secs_f64(took / 1000.0).ok()?,
+        })
+    }
+
+    /// Reproduce recorded response times
+    pub fn set_realtime(&self, realtime: bool) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).realtime = realtime;
+    }
+
+    /// Create a reader object serving the session
+    pub fn reader(&self) -> UemReader {
+        Arc::new(Mutex::new(ReaderReplay { state: self.state.clone() }))
+    }
+
+    /// Check that the whole session has been replayed
+    /// and no unexpected command has been received
+    pub fn is_done(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.transactions.is_empty() && state.mismatches.is_empty()
+    }
+
+    /// Panic if some transactions are left or
+    /// unexpected commands have been received
+    pub fn assert_done(&self) {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        assert!(state.mismatches.is_empty(), "session replay: {}", state.mismatches.join("; "));
+        assert!(state.transactions.is_empty(),
+            "session replay: {} transaction(s) left, next is {:02X?}",
+            state.transactions.len(), state.transactions[0].command);
+    }
+}
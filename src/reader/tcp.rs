@@ -0,0 +1,252 @@
+//! TCP reader interface
+//!
+//! Used for readers behind serial device servers
+//! and Ethernet gateways, which pass protocol frames
+//! through a TCP connection unchanged.
+
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::reader::*;
+use crate::reader::processing::*;
+
+#[derive(Debug, Clone, Copy)]
+/// Network connection parameters
+pub struct UemTcpParameters {
+    /// Time to wait for a connection to be established
+    pub connect_timeout: Duration,
+    /// Time to wait for each part of a reader response
+    pub read_timeout: Duration,
+    /// Reconnect automatically if the connection is lost
+    pub reconnect: bool,
+}
+
+impl Default for UemTcpParameters {
+    fn default() -> UemTcpParameters {
+        UemTcpParameters {
+            connect_timeout: TIMEOUT,
+            read_timeout: TIMEOUT,
+            reconnect: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ReaderTcp {
+    address: String,
+    parameters: UemTcpParameters,
+    stream: Option<TcpStream>,
+    connected: bool,
+    frame: Vec<u8>,
+    assembler: FrameAssembler,
+    ncommand: u8,
+}
+
+impl CommandsCounter for ReaderTcp {
+    fn commands_count(&self) -> u8 {
+        self.ncommand
+    }
+
+    fn increment_commands(&mut self) {
+        if self.commands_count() == u8::MAX {
+            self.ncommand = 0;
+        }
+        self.ncommand += 1;
+    }
+}
+
+impl ReaderTcp {
+    fn connect(&mut self) -> UemResult {
+        let addresses = self.address.to_socket_addrs()
+            .map_err(|_| UemError::IncorrectReaderName)?;
+        for address in addresses {
+            let stream = match TcpStream::connect_timeout(&address, self.parameters.connect_timeout) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            if stream.set_read_timeout(Some(self.parameters.read_timeout)).is_err() ||
+                stream.set_write_timeout(Some(self.parameters.read_timeout)).is_err() {
+                continue;
+            }
+            stream.set_nodelay(true).ok();
+            self.stream = Some(stream);
+            return Ok(());
+        }
+        Err(UemError::ReaderConnectionFailed)
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> UemResult {
+        if self.stream.is_none() && self.parameters.reconnect {
+            self.connect()?;
+        }
+        let stream = self.stream.as_mut().ok_or(UemError::ReaderNotConnected)?;
+        if stream.write_all(frame).is_ok() {
+            return Ok(());
+        }
+        self.stream = None;
+        if !self.parameters.reconnect {
+            return Err(UemError::NotTransacted);
+        }
+        // The frame has not been delivered, so it is safe
+        // to repeat it over a fresh connection
+        self.connect()?;
+        let stream = self.stream.as_mut().ok_or(UemError::ReaderNotConnected)?;
+        stream.write_all(frame).map_err(|_| {
+            UemError::NotTransacted
+        })
+    }
+}
+
+impl ReaderTcp {
+    /// Send a command frame and collect the response
+    /// frame, reusing buffers of the reader object
+    fn transact(&mut self, command: &[u8]) -> UemResult {
+        if !self.connected {
+            return Err(UemError::ReaderNotConnected);
+        }
+        if command.is_empty() {
+            return Err(UemError::IncorrectParameter);
+        }
+
+        let (address, counter) = (self.device_address(), self.commands_count());
+        let mut send_buffer = std::mem::take(&mut self.frame);
+        prepare_command(self, command, &mut send_buffer);
+        let written = self.write_frame(&send_buffer);
+        self.frame = send_buffer;
+        written?;
+
+        let stream = self.stream.as_mut().ok_or(UemError::ReaderNotConnected)?;
+
+        let res = receive_response(stream, &mut self.assembler, self.parameters.read_timeout, address, counter);
+        // Late response would break the next transaction,
+        // so start over with a fresh connection
+        if res.is_err() && self.parameters.reconnect {
+            self.stream = None;
+        }
+        res
+    }
+}
+
+impl UemReaderInternalTrait for ReaderTcp {
+    /// Connect to a remote reader
+    fn open(&mut self) -> UemResult {
+        if self.connected {
+            return Err(UemError::ReaderAlreadyConnected);
+        }
+        self.connect()?;
+        self.connected = true;
+        Ok(())
+    }
+
+    /// Close connection to a remote reader
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        if !self.connected {
+            return Err(UemError::ReaderNotConnected);
+        }
+        self.stream = None;
+        self.connected = false;
+        Ok(())
+    }
+
+    /// Send command to a remote reader and receive response
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        self.transact(command)?;
+        parse_response(command, self.assembler.frame_mut()).map(|r| r.to_vec())
+    }
+
+    /// Send command to a remote reader and receive response
+    /// without allocating memory
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        self.transact(command)?;
+        copy_response(parse_response(command, self.assembler.frame_mut())?, response)
+    }
+
+    /// Set response timeout
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        if timeout.is_zero() {
+            return Err(UemError::IncorrectParameter);
+        }
+        if let Some(stream) = self.stream.as_ref() {
+            stream.set_read_timeout(Some(timeout)).map_err(|_| UemError::IncorrectParameter)?;
+        }
+        self.parameters.read_timeout = timeout;
+        Ok(())
+    }
+
+    /// Current response timeout
+    fn timeout(&self) -> Duration {
+        self.parameters.read_timeout
+    }
+
+    /// Set counter of the next command
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        self.ncommand = count;
+        Ok(())
+    }
+}
+
+/// Create a reader object for a network-attached reader
+///
+/// The connection is not established until
+/// [`open`](UemReaderInternalTrait::open)
+/// is called on the returned reader.
+///
+/// # Arguments
+///
+/// * `address` - Remote address, e.g. `192.168.0.10:4001`
+/// * `parameters` - A reference to a set of [connection parameters](UemTcpParameters)
+///
+/// # Example
+///
+/// ```
+/// # use std::io::{Read, Write};
+/// # use std::net::TcpListener;
+/// # use uem_reader::reader::UemReaderInternalTrait;
+/// # use uem_reader::reader::{processing::*, tcp::*};
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// // Local stand-in answering version requests
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap().to_string();
+/// std::thread::spawn(move || {
+///     let (mut stream, _) = listener.accept().unwrap();
+///     let mut frame = vec![];
+///     let mut byte = [0u8; 1];
+///     while stream.read_exact(&mut byte).is_ok() {
+///         frame.push(byte[0]);
+///         if byte[0] != 0xFE {
+///             continue;
+///         }
+///         let (device, counter, command) = parse_command(&frame).unwrap();
+///         // Late response to a previous command is dropped by the reader object
+///         let stale = [command[0], 0x00, 6, 5, 4, 3, 2, 1];
+///         stream.write_all(&prepare_response(device, counter.wrapping_sub(1), &stale)).unwrap();
+///         let data = [command[0], 0x00, 1, 2, 3, 4, 5, 6];
+///         stream.write_all(&prepare_response(device, counter, &data)).unwrap();
+///         frame.clear();
+///     }
+/// });
+///
+/// let mut uem_reader = new_tcp_reader(&address, &UemTcpParameters::default());
+///
+/// if uem_reader.open().is_err() {
+///     return;
+/// }
+///
+/// let version = uem_reader.commands().reader().get_version();
+/// assert_eq!(version.unwrap(), vec![1, 2, 3, 4, 5, 6]);
+///
+/// # uem_reader.close().unwrap();
+/// ```
+pub fn new_tcp_reader(address: &str, parameters: &UemTcpParameters) -> UemReader {
+    Arc::new(Mutex::new(ReaderTcp {
+        address: address.to_string(),
+        parameters: *parameters,
+        stream: None,
+        connected: false,
+        frame: Vec::new(),
+        assembler: FrameAssembler::default(),
+        ncommand: initial_commands_count(),
+    }))
+}
@@ -1,30 +1,222 @@
 //! USB reader implementation
 
 //use core::slice::SlicePattern;
-use std::sync::{Arc, Mutex};
-use std::{time::Duration};
-use rand::Rng;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use rusb::{
-    DeviceHandle, DeviceList, Language, 
-    Device, UsbContext, Direction,
+    Context, DeviceHandle, Language, 
+    Device, UsbContext, Direction, TransferType,
 };
 
 use crate::reader::*;
 use crate::reader::processing::*;
 use crate::errors::*;
 
+pub mod hotplug;
+pub mod reconnect;
+
 const UEM_VID: u16 = 0xC251;
 const UEM_PID: u16 = 0x130A;
 
-#[derive(Debug, Default)]
+/// Size of a single bulk read, a multiple
+/// of any possible bulk endpoint packet size
+const USB_PACKET_BUFFER: usize = 512;
+
+static USB_MODELS: OnceLock<Mutex<Vec<UemUsbModel>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Reader model identified by USB vendor and product IDs
+pub struct UemUsbModel {
+    /// USB vendor ID
+    pub vendor_id: u16,
+    /// USB product ID
+    pub product_id: u16,
+    /// Human readable model name
+    pub name: String,
+    /// Interface to use on composite devices.
+    /// If not set, the first interface having
+    /// both IN and OUT endpoints is used.
+    pub interface: Option<u8>,
+}
+
+fn usb_models_registry() -> &'static Mutex<Vec<UemUsbModel>> {
+    USB_MODELS.get_or_init(|| Mutex::new(vec![
+        UemUsbModel {
+            vendor_id: UEM_VID,
+            product_id: UEM_PID,
+            name: "MicroEM RFID reader".to_string(),
+            interface: None,
+        },
+    ]))
+}
+
+/// Add a reader model to the registry of known models
+/// 
+/// The registry is used by default for all USB
+/// discovery functions. A model with the same
+/// vendor and product IDs is replaced.
+/// 
+/// # Arguments
+///
+/// * `model` - A [model](UemUsbModel) to register
+/// 
+/// # Example
+/// 
+/// ```
+/// # use uem_reader::reader::usb::*;
+/// register_usb_model(UemUsbModel {
+///     vendor_id: 0xC251,
+///     product_id: 0x130B,
+///     name: "Custom reader".to_string(),
+///     // Reader function is on the second interface
+///     interface: Some(1),
+/// });
+/// assert!(usb_models().iter().any(|m| m.product_id == 0x130B));
+/// ```
+pub fn register_usb_model(model: UemUsbModel) {
+    let mut models = usb_models_registry().lock().unwrap_or_else(|e| e.into_inner());
+    models.retain(|m| m.vendor_id != model.vendor_id || m.product_id != model.product_id);
+    models.push(model);
+}
+
+/// List reader models currently known to the registry
+pub fn usb_models() -> Vec<UemUsbModel> {
+    usb_models_registry().lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Set of reader models to look for during discovery
+/// 
+/// The default filter contains all [registered](register_usb_model) models.
+pub struct UemUsbFilter {
+    /// Accepted reader [models](UemUsbModel)
+    pub models: Vec<UemUsbModel>,
+}
+
+impl Default for UemUsbFilter {
+    fn default() -> UemUsbFilter {
+        UemUsbFilter {
+            models: usb_models(),
+        }
+    }
+}
+
+impl UemUsbFilter {
+    /// Find a model matching specific vendor and product IDs
+    pub fn model(&self, vendor_id: u16, product_id: u16) -> Option<&UemUsbModel> {
+        self.models.iter().find(|m| m.vendor_id == vendor_id && m.product_id == product_id)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Identity of a USB reader
+pub struct UemUsbReaderInfo {
+    /// Registry [model](UemUsbModel) the reader matched
+    pub model: UemUsbModel,
+    /// Number of the bus the reader is connected to
+    pub bus_number: u8,
+    /// Port numbers from the root hub to the reader
+    pub port_numbers: Vec<u8>,
+    /// Device address on the bus
+    pub address: u8,
+    /// Manufacturer string descriptor
+    pub manufacturer: Option<String>,
+    /// Product string descriptor
+    pub product: Option<String>,
+    /// Serial number string descriptor
+    pub serial: Option<String>,
+}
+
+impl UemUsbReaderInfo {
+    /// Port path of a reader in `bus-port.port` form,
+    /// e.g. `1-2.4`, as used by Linux sysfs
+    pub fn path(&self) -> String {
+        let ports: Vec<String> = self.port_numbers.iter().map(|p| p.to_string()).collect();
+        format!("{}-{}", self.bus_number, ports.join("."))
+    }
+
+    fn from_device<T: UsbContext>(device: &Device<T>, model: &UemUsbModel) -> Self {
+        UemUsbReaderInfo {
+            model: model.clone(),
+            bus_number: device.bus_number(),
+            port_numbers: device.port_numbers().unwrap_or_default(),
+            address: device.address(),
+            ..Default::default()
+        }
+    }
+
+    fn read_strings<T: UsbContext>(&mut self, handle: &DeviceHandle<T>, language: Language) {
+        let device_desc = match handle.device().device_descriptor() {
+            Ok(d) => d,
+            Err(_) => return,
+        };
+        self.manufacturer = handle.read_manufacturer_string(language, &device_desc, TIMEOUT).ok();
+        self.product = handle.read_product_string(language, &device_desc, TIMEOUT).ok();
+        self.serial = handle.read_serial_number_string(language, &device_desc, TIMEOUT).ok();
+    }
+}
+
+#[derive(Debug)]
 struct ReaderUsb<T: UsbContext> {
     handle: Option<DeviceHandle<T>>,
     device: Option<Device<T>>,
+    info: UemUsbReaderInfo,
     language: Option<Language>,
     timeout: Duration,
+    endpoints: UsbEndpoints,
+    kernel_driver_detached: bool,
+    frame: Vec<u8>,
+    packet: Vec<u8>,
+    assembler: FrameAssembler,
+    ncommand: u8,
+}
+
+/// Interface and endpoints used to talk to a reader
+#[derive(Debug, Clone, Copy)]
+struct UsbEndpoints {
+    interface: u8,
+    alt_setting: u8,
     ep_in_addr: u8,
     ep_out_addr: u8,
-    ncommand: u8,
+}
+
+impl UsbEndpoints {
+    /// Pick the first interface having both IN and OUT
+    /// bulk or interrupt endpoints. If `interface` is set,
+    /// only that interface is considered.
+    fn select<T: UsbContext>(device: &Device<T>, interface: Option<u8>) -> Option<Self> {
+        let config_desc = device.active_config_descriptor()
+            .or_else(|_| device.config_descriptor(0))
+            .ok()?;
+
+        for iface in config_desc.interfaces() {
+            if interface.is_some_and(|n| n != iface.number()) {
+                continue;
+            }
+            for interface_desc in iface.descriptors() {
+                let mut ep_in_addr = None;
+                let mut ep_out_addr = None;
+                for endpoint_desc in interface_desc.endpoint_descriptors() {
+                    if !matches!(endpoint_desc.transfer_type(), TransferType::Bulk | TransferType::Interrupt) {
+                        continue;
+                    }
+                    match endpoint_desc.direction() {
+                        Direction::In => { ep_in_addr.get_or_insert(endpoint_desc.address()); },
+                        Direction::Out => { ep_out_addr.get_or_insert(endpoint_desc.address()); },
+                    }
+                }
+                if let (Some(ep_in_addr), Some(ep_out_addr)) = (ep_in_addr, ep_out_addr) {
+                    return Some(UsbEndpoints {
+                        interface: interface_desc.interface_number(),
+                        alt_setting: interface_desc.setting_number(),
+                        ep_in_addr,
+                        ep_out_addr,
+                    });
+                }
+            }
+        }
+        None
+    }
 }
 
 impl<T: UsbContext> CommandsCounter for ReaderUsb<T> {
@@ -40,6 +232,127 @@ impl<T: UsbContext> CommandsCounter for ReaderUsb<T> {
     }
 }
 
+impl<T: UsbContext> ReaderUsb<T> {
+    /// Read string descriptors of a reader not opened yet,
+    /// if the device can be opened by the current user
+    fn read_identity(&mut self) {
+        let handle = match self.device.as_ref().map(|d| d.open()) {
+            Some(Ok(h)) => h,
+            _ => return,
+        };
+        if let Some(language) = handle.read_languages(TIMEOUT).ok().and_then(|l| l.first().cloned()) {
+            self.info.read_strings(&handle, language);
+        }
+    }
+
+    /// Take the reader interface from the kernel driver if needed
+    /// and claim it for the library
+    fn claim(&mut self, handle: &mut DeviceHandle<T>) -> UemResult {
+        let interface = self.endpoints.interface;
+        if let Ok(true) = handle.kernel_driver_active(interface) {
+            handle.detach_kernel_driver(interface).map_err(|_| UemError::Access)?;
+            self.kernel_driver_detached = true;
+        }
+        handle.claim_interface(interface).map_err(|_| UemError::Access)?;
+        if self.endpoints.alt_setting != 0 {
+            handle.set_alternate_setting(interface, self.endpoints.alt_setting)
+                .map_err(|_| UemError::Access)?;
+        }
+        Ok(())
+    }
+
+    /// Give the reader interface back to the system
+    fn release(&mut self, handle: &mut DeviceHandle<T>) {
+        handle.release_interface(self.endpoints.interface).ok();
+        if self.kernel_driver_detached {
+            handle.attach_kernel_driver(self.endpoints.interface).ok();
+            self.kernel_driver_detached = false;
+        }
+    }
+
+    /// Bring an endpoint back to work after a failed transfer.
+    /// Stalled endpoint is cleared, if this does not help
+    /// the device is reset and the interface is claimed again.
+    fn recover(&mut self, endpoint: u8, error: rusb::Error) {
+        if error != rusb::Error::Pipe {
+            return;
+        }
+        let mut handle = match self.handle.take() {
+            Some(h) => h,
+            None => return,
+        };
+        if handle.clear_halt(endpoint).is_err() && handle.reset().is_ok() {
+            self.kernel_driver_detached = false;
+            self.claim(&mut handle).ok();
+        }
+        self.handle = Some(handle);
+    }
+
+    /// Write a command frame to the OUT endpoint
+    fn write_frame(&mut self, frame: &[u8]) -> UemResult {
+        let ep_out_addr = self.endpoints.ep_out_addr;
+        let handle = self.handle.as_ref().ok_or(UemError::ReaderNotConnected)?;
+        match handle.write_bulk(ep_out_addr, frame, self.timeout) {
+            Ok(_) => Ok(()),
+            Err(rusb::Error::NoDevice) => Err(UemError::ReaderDisconnected),
+            Err(e) => {
+                self.recover(ep_out_addr, e);
+                Err(UemError::NotTransacted)
+            }
+        }
+    }
+
+    /// Read bulk packets until the response to a command sent
+    /// with `counter` is collected or the response deadline expires
+    fn receive_response(&mut self, counter: u8) -> UemResult {
+        let ep_in_addr = self.endpoints.ep_in_addr;
+        let address = self.device_address();
+        let deadline = Instant::now() + self.timeout;
+        self.assembler.clear();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(UemError::ReaderTimeout);
+            }
+            let handle = self.handle.as_ref().ok_or(UemError::ReaderNotConnected)?;
+            let count = match handle.read_bulk(ep_in_addr, &mut self.packet, remaining) {
+                Ok(c) => c,
+                Err(rusb::Error::NoDevice) => return Err(UemError::ReaderDisconnected),
+                Err(rusb::Error::Timeout) => return Err(UemError::ReaderTimeout),
+                Err(e) => {
+                    self.recover(ep_in_addr, e);
+                    return Err(UemError::ReaderResponseFailure);
+                }
+            };
+            self.assembler.push(&self.packet[..count])?;
+            // Late responses to previous commands are dropped
+            if find_response(&mut self.assembler, address, counter) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Send a command frame and collect the response
+    /// frame, reusing buffers of the reader object
+    fn transact(&mut self, command: &[u8]) -> UemResult {
+        if self.handle.is_none() {
+            return Err(UemError::ReaderNotConnected);
+        }
+        if command.is_empty() {
+            return Err(UemError::IncorrectParameter);
+        }
+
+        let counter = self.commands_count();
+        let mut send_buffer = std::mem::take(&mut self.frame);
+        prepare_command(self, command, &mut send_buffer);
+        let res = self.write_frame(&send_buffer);
+        self.frame = send_buffer;
+        res?;
+
+        self.receive_response(counter)
+    }
+}
+
 impl<T: UsbContext> UemReaderInternalTrait for ReaderUsb<T> {
     //#![warn(missing_docs)]
     /// Open USB interface
@@ -47,14 +360,24 @@ impl<T: UsbContext> UemReaderInternalTrait for ReaderUsb<T> {
         if self.handle.is_some() {
             return Err(UemError::ReaderAlreadyConnected);
         }
-        if let Ok(h) = self.device.take().unwrap().open() {
+        let device = self.device.as_ref().ok_or(UemError::ReaderConnectionFailed)?;
+        if let Ok(mut h) = device.open() {
             if let Ok(l) = h.read_languages(TIMEOUT) {
                 if !l.is_empty() {
                     self.language = Some(l[0]);
                 }
             }
+            if let Some(language) = self.language {
+                if self.info.serial.is_none() {
+                    self.info.read_strings(&h, language);
+                }
+            }
+            if let Err(e) = self.claim(&mut h) {
+                self.release(&mut h);
+                return Err(e);
+            }
+            self.device = None;
             self.handle = Some(h);
-            self.timeout = TIMEOUT;
             return Ok(())
         }
         Err(UemError::ReaderConnectionFailed)
@@ -65,7 +388,8 @@ impl<T: UsbContext> UemReaderInternalTrait for ReaderUsb<T> {
         if self.handle.is_none() {
             return Err(UemError::ReaderNotConnected);
         }
-        if let Some(h) = self.handle.take() {
+        if let Some(mut h) = self.handle.take() {
+            self.release(&mut h);
             self.device = Some(h.device());
             return Ok(())
         }
@@ -74,60 +398,60 @@ impl<T: UsbContext> UemReaderInternalTrait for ReaderUsb<T> {
 
     /// Send command directly to a USB reader
     fn send(&mut self, command: &[u8]) -> UemResultVec {
-        
-        if self.handle.is_none() {
-            return Err(UemError::ReaderNotConnected);
-        }
-        if command.is_empty() {
-            return Err(UemError::IncorrectParameter);
-        }
-
-        let send_buffer = prepare_command(self, &command);
-        if send_buffer.is_empty() {
-            return Err(UemError::IncorrectParameter);
-        }
-
-        let handle = self.handle.as_mut().unwrap();
-
-        handle.claim_interface(0).map_err(|_| UemError::Access)?;
-
-        let mut res = handle.write_bulk(self.ep_out_addr, send_buffer.as_slice(), TIMEOUT);
-
-        if res.is_err() {
-            return Err(UemError::NotTransacted);
-        }
-
-        let mut receive_buffer = vec![0u8; 256];
-
-        res = handle.read_bulk(self.ep_in_addr, &mut receive_buffer, TIMEOUT);
+        self.transact(command)?;
+        parse_response(command, self.assembler.frame_mut()).map(|r| r.to_vec())
+    }
 
-        handle.release_interface(0).map_err(|_| UemError::Access)?;
+    /// Send command directly to a USB reader
+    /// without allocating memory
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        self.transact(command)?;
+        copy_response(parse_response(command, self.assembler.frame_mut())?, response)
+    }
 
-        if res.is_err() {
-            return Err(UemError::ReaderResponseFailure);
+    /// Set response timeout
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        if timeout.is_zero() {
+            return Err(UemError::IncorrectParameter);
         }
+        self.timeout = timeout;
+        Ok(())
+    }
 
-        let response_length = res.unwrap();
+    /// Current response timeout
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
 
-        if response_length <= 6 {
-            return Err(UemError::ReaderResponseFailure);
-        }
+    /// Set counter of the next command
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        self.ncommand = count;
+        Ok(())
+    }
+}
 
-        let response = parse_response(&receive_buffer[..response_length].to_vec())?;
+/// Build a reader object for a USB device
+/// if it is a MicroEM reader
+fn reader_from_device<T: UsbContext>(device: Device<T>, filter: &UemUsbFilter) -> Option<ReaderUsb<T>> {
+    let device_desc = device.device_descriptor().ok()?;
 
-        if (response.len() < 2) || (response[0] != command[0]) {
-            return Err(UemError::ReaderIncorrectResponse);
-        }
+    let model = filter.model(device_desc.vendor_id(), device_desc.product_id())?;
 
-        if response[1] != 0x00 {
-            if response.len() == 2 {
-                return Err(UemError::ReaderUnsuccessful(UemInternalError::from_byte(response[1]), None));
-            }
-            return Err(UemError::ReaderUnsuccessful(UemInternalError::from_byte(response[1]), Some(response[2..].to_vec())));
-        }
+    let endpoints = UsbEndpoints::select(&device, model.interface)?;
 
-        Ok(response[2..].to_vec())
-    }
+    Some(ReaderUsb {
+        handle: None,
+        info: UemUsbReaderInfo::from_device(&device, model),
+        device: Some(device),
+        language: None,
+        timeout: TIMEOUT,
+        endpoints,
+        kernel_driver_detached: false,
+        frame: Vec::with_capacity(USB_PACKET_BUFFER),
+        packet: vec![0u8; USB_PACKET_BUFFER],
+        assembler: FrameAssembler::default(),
+        ncommand: initial_commands_count(),
+    })
 }
 
 /// Search system for MicroEM readers on USB ports
@@ -158,47 +482,149 @@ impl<T: UsbContext> UemReaderInternalTrait for ReaderUsb<T> {
 /// let uem_reader = uem_reader.unwrap();
 /// ```
 pub fn find_usb_readers() -> Vec<UemReader> {
-    let mut usb_readers: Vec<UemReader> = Vec::new();
-    let devices = DeviceList::new();
-    if let Err(_) = devices {
-        return usb_readers;
-    }
-    for device in devices.unwrap().iter() {
-        let device_desc = match device.device_descriptor() {
-            Ok(d) => d,
-            Err(_) => continue,
-        };
-
-        if  device_desc.vendor_id() != UEM_VID || 
-            device_desc.product_id() != UEM_PID {
-            continue
-        }
+    // Devices are not opened until the reader is
+    usb_readers(&UemUsbFilter::default())
+        .into_iter()
+        .map(|r| Arc::new(Mutex::new(r)) as UemReader)
+        .collect()
+}
 
-        let mut usb_reader = ReaderUsb {
-            ncommand: rand::thread_rng().gen(),
-            ..Default::default()
-        };
+/// Search system for MicroEM readers on USB ports
+/// along with their [identity](UemUsbReaderInfo)
+/// 
+/// String descriptors are read if the
+/// device can be opened by the current user.
+/// 
+/// # Example
+/// 
+/// ```
+/// # use uem_reader::reader::usb::list_usb_readers;
+/// for (info, _uem_reader) in list_usb_readers() {
+///     println!("{} {:?} {:?}", info.path(), info.product, info.serial);
+/// }
+/// ```
+pub fn list_usb_readers() -> Vec<(UemUsbReaderInfo, UemReader)> {
+    list_usb_readers_with(&UemUsbFilter::default())
+}
 
-        for n in 0..device_desc.num_configurations() {
-            let config_desc = match device.config_descriptor(n) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
+/// Search system for USB readers of specific models
+/// 
+/// # Arguments
+///
+/// * `filter` - A reference to a [set of models](UemUsbFilter) to look for
+/// 
+/// # Example
+/// 
+/// ```
+/// # use uem_reader::reader::usb::*;
+/// // Look for a single model only
+/// let filter = UemUsbFilter {
+///     models: vec![UemUsbModel {
+///         vendor_id: 0xC251,
+///         product_id: 0x130A,
+///         name: "MicroEM RFID reader".to_string(),
+///         ..Default::default()
+///     }],
+/// };
+/// for (info, _uem_reader) in list_usb_readers_with(&filter) {
+///     println!("{}: {}", info.path(), info.model.name);
+/// }
+/// ```
+pub fn list_usb_readers_with(filter: &UemUsbFilter) -> Vec<(UemUsbReaderInfo, UemReader)> {
+    usb_readers(filter)
+        .into_iter()
+        .map(|mut r| {
+            r.read_identity();
+            (r.info.clone(), Arc::new(Mutex::new(r)) as UemReader)
+        })
+        .collect()
+}
 
-            for interface in config_desc.interfaces() {
-                for interface_desc in interface.descriptors() {
-                    for endpoint_desc in interface_desc.endpoint_descriptors() {
-                        match endpoint_desc.direction() {
-                            Direction::In => usb_reader.ep_in_addr = endpoint_desc.address(),
-                            Direction::Out => usb_reader.ep_out_addr = endpoint_desc.address()
-                        }
-                    }
-                }
-            }
+/// Collect reader objects for all matching USB devices
+/// without opening them, so their string descriptors are unknown
+fn usb_readers(filter: &UemUsbFilter) -> Vec<ReaderUsb<Context>> {
+    let mut usb_readers: Vec<ReaderUsb<Context>> = Vec::new();
+    let context = match Context::new() {
+        Ok(c) => c,
+        Err(_) => return usb_readers,
+    };
+    let devices = match context.devices() {
+        Ok(d) => d,
+        Err(_) => return usb_readers,
+    };
+    for device in devices.iter() {
+        if let Some(usb_reader) = reader_from_device(device, filter) {
+            usb_readers.push(usb_reader);
         }
-        usb_reader.device = Some(device);
-        usb_readers.push(Arc::new(Mutex::new(usb_reader)));
     }
 
     usb_readers
-}
\ No newline at end of file
+}
+
+/// Open a USB reader with specific serial number
+/// 
+/// # Arguments
+///
+/// * `serial` - Serial number string descriptor of the reader
+/// 
+/// # Returns
+/// 
+/// `Ok(UemReader)` with opened reader on success,
+/// [`UemError::IncorrectReaderName`](UemError::IncorrectReaderName)
+/// if there is no such reader, otherwise returns an error.
+/// 
+/// # Example
+/// 
+/// ```no_run
+/// # use uem_reader::reader::usb::open_usb_reader_by_serial;
+/// let uem_reader = open_usb_reader_by_serial("0123456789");
+/// 
+/// if uem_reader.is_err() {
+///     return;
+/// }
+/// ```
+pub fn open_usb_reader_by_serial(serial: &str) -> core::result::Result<UemReader, UemError> {
+    open_usb_reader_with(|usb_reader| {
+        usb_reader.read_identity();
+        usb_reader.info.serial.as_deref() == Some(serial)
+    })
+}
+
+/// Open a USB reader connected to specific port
+/// 
+/// # Arguments
+///
+/// * `path` - Port path in `bus-port.port` form,
+///   see [`UemUsbReaderInfo::path`](UemUsbReaderInfo::path)
+/// 
+/// # Returns
+/// 
+/// `Ok(UemReader)` with opened reader on success,
+/// [`UemError::IncorrectReaderName`](UemError::IncorrectReaderName)
+/// if there is no reader on the port, otherwise returns an error.
+/// 
+/// # Example
+/// 
+/// ```no_run
+/// # use uem_reader::reader::usb::open_usb_reader_by_path;
+/// let uem_reader = open_usb_reader_by_path("1-2.4");
+/// 
+/// if uem_reader.is_err() {
+///     return;
+/// }
+/// ```
+pub fn open_usb_reader_by_path(path: &str) -> core::result::Result<UemReader, UemError> {
+    open_usb_reader_with(|usb_reader| usb_reader.info.path() == path)
+}
+
+/// Open the first reader accepted by `matches`,
+/// devices after it are left untouched
+fn open_usb_reader_with(mut matches: impl FnMut(&mut ReaderUsb<Context>) -> bool) -> core::result::Result<UemReader, UemError> {
+    let usb_reader = usb_readers(&UemUsbFilter::default())
+        .into_iter()
+        .find_map(|mut r| matches(&mut r).then_some(r))
+        .ok_or(UemError::IncorrectReaderName)?;
+    let mut uem_reader: UemReader = Arc::new(Mutex::new(usb_reader));
+    uem_reader.open()?;
+    Ok(uem_reader)
+}
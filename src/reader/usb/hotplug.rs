@@ -0,0 +1,158 @@
+//! Monitoring of MicroEM readers being
+//! plugged in and out of USB ports
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use rusb::{Context, Device, Hotplug, HotplugBuilder, Registration, UsbContext};
+
+use crate::reader::*;
+use crate::reader::usb::*;
+
+/// Period to check for the watcher being stopped
+const EVENTS_PERIOD: Duration = Duration::from_millis(100);
+
+type WeakReader = Weak<Mutex<dyn UemReaderInternalTrait+Send>>;
+
+/// USB reader presence change
+pub enum UemUsbEvent {
+    /// A reader has been plugged in.
+    /// Contains a closed reader object ready to be opened.
+    Arrived(UemReader),
+    /// A reader has been unplugged.
+    /// Contains the reader object previously reported
+    /// on arrival, if the application still holds it.
+    Left(UemReader),
+}
+
+struct HotplugHandler {
+    filter: UemUsbFilter,
+    events: Sender<UemUsbEvent>,
+    readers: HashMap<(u8, u8), WeakReader>,
+}
+
+impl Hotplug<Context> for HotplugHandler {
+    fn device_arrived(&mut self, device: Device<Context>) {
+        let location = (device.bus_number(), device.address());
+        if let Some(usb_reader) = reader_from_device(device, &self.filter) {
+            let uem_reader: UemReader = Arc::new(Mutex::new(usb_reader));
+            self.readers.insert(location, Arc::downgrade(&uem_reader));
+            self.events.send(UemUsbEvent::Arrived(uem_reader)).ok();
+        }
+    }
+
+    fn device_left(&mut self, device: Device<Context>) {
+        let location = (device.bus_number(), device.address());
+        if let Some(uem_reader) = self.readers.remove(&location).and_then(|r| r.upgrade()) {
+            self.events.send(UemUsbEvent::Left(uem_reader)).ok();
+        }
+    }
+}
+
+/// Watcher of MicroEM readers on USB ports
+///
+/// Readers already present in the system are reported
+/// as arrived right after the watcher is started.
+/// Monitoring stops when the watcher is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use uem_reader::reader::usb::hotplug::*;
+/// let uem_watcher = UemUsbWatcher::new();
+///
+/// if uem_watcher.is_err() {
+///     return;
+/// }
+///
+/// let uem_watcher = uem_watcher.unwrap();
+/// let mut uem_readers = vec![];
+///
+/// for event in uem_watcher.events() {
+///     match event {
+///         UemUsbEvent::Arrived(uem_reader) => uem_readers.push(uem_reader),
+///         UemUsbEvent::Left(uem_reader) =>
+///             uem_readers.retain(|r| !Arc::ptr_eq(r, &uem_reader)),
+///     }
+/// }
+/// ```
+pub struct UemUsbWatcher {
+    events: Receiver<UemUsbEvent>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    registration: Option<Registration<Context>>,
+}
+
+impl UemUsbWatcher {
+    /// Start monitoring USB ports for
+    /// [registered](crate::reader::usb::register_usb_model) reader models
+    ///
+    /// # Returns
+    ///
+    /// `Ok(UemUsbWatcher)` on success, otherwise returns
+    /// [`UemError::UnsupportedFeature`](UemError::UnsupportedFeature)
+    /// if the platform lacks hotplug support, or another error.
+    pub fn new() -> core::result::Result<Self, UemError> {
+        Self::with_filter(&UemUsbFilter::default())
+    }
+
+    /// Start monitoring USB ports for specific reader models
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - A reference to a [set of models](UemUsbFilter) to look for
+    pub fn with_filter(filter: &UemUsbFilter) -> core::result::Result<Self, UemError> {
+        if !rusb::has_hotplug() {
+            return Err(UemError::UnsupportedFeature);
+        }
+        let context = Context::new().map_err(|_| UemError::Access)?;
+        let (sender, events) = channel();
+        let registration = HotplugBuilder::new()
+            .enumerate(true)
+            .register(&context, Box::new(HotplugHandler {
+                filter: filter.clone(),
+                events: sender,
+                readers: HashMap::new(),
+            }))
+            .map_err(|_| UemError::Access)?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let thread = std::thread::spawn(move || {
+            while thread_running.load(Ordering::Relaxed) {
+                if context.handle_events(Some(EVENTS_PERIOD)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(UemUsbWatcher {
+            events,
+            running,
+            thread: Some(thread),
+            registration: Some(registration),
+        })
+    }
+
+    /// Channel delivering [reader events](UemUsbEvent)
+    ///
+    /// Receiving from the channel blocks until
+    /// a reader is plugged in or out.
+    pub fn events(&self) -> &Receiver<UemUsbEvent> {
+        &self.events
+    }
+}
+
+impl Drop for UemUsbWatcher {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+        self.registration.take();
+    }
+}
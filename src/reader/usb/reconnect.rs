@@ -0,0 +1,248 @@
+//! USB readers which survive being
+//! unplugged and plugged back in
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use rusb::Context;
+
+use crate::reader::*;
+use crate::reader::usb::*;
+
+#[derive(Debug, Clone)]
+/// Reader connection change reported by
+/// an automatically reconnecting reader
+pub enum UemUsbReconnectEvent {
+    /// The reader has been disconnected
+    Disconnected,
+    /// The reader has been found again and reopened,
+    /// contains its current [identity](UemUsbReaderInfo)
+    Reconnected(UemUsbReaderInfo),
+}
+
+/// Time between the reader loss and the first
+/// reconnection attempt, doubled after every failed one
+const RECONNECT_DELAY: Duration = Duration::from_millis(250);
+/// Longest time between reconnection attempts
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(4);
+
+type ReconnectCallback = Box<dyn FnMut(UemUsbReconnectEvent) + Send>;
+
+struct ReaderUsbReconnecting {
+    serial: String,
+    filter: UemUsbFilter,
+    reader: Option<ReaderUsb<Context>>,
+    connected: bool,
+    lost: bool,
+    timeout: Duration,
+    delay: Duration,
+    next_attempt: Option<Instant>,
+    on_event: ReconnectCallback,
+}
+
+impl ReaderUsbReconnecting {
+    /// Look for the reader with the same serial number
+    /// and open it with a fresh command counter
+    fn reconnect(&mut self) -> UemResult {
+        let mut usb_reader = usb_readers(&self.filter)
+            .into_iter()
+            .find_map(|mut r| {
+                r.read_identity();
+                (r.info.serial.as_deref() == Some(self.serial.as_str())).then_some(r)
+            })
+            .ok_or(UemError::ReaderDisconnected)?;
+        usb_reader.set_timeout(self.timeout)?;
+        usb_reader.open()?;
+        let info = usb_reader.info.clone();
+        self.reader = Some(usb_reader);
+        if self.lost {
+            self.lost = false;
+            (self.on_event)(UemUsbReconnectEvent::Reconnected(info));
+        }
+        Ok(())
+    }
+}
+
+impl ReaderUsbReconnecting {
+    /// Drop the lost reader, it is looked for again
+    /// after a delay, since the device is usually still
+    /// enumerated right after the loss. The command may have
+    /// reached the reader before it was lost, so it is not
+    /// repeated after reconnection.
+    fn lost(&mut self) {
+        self.reader = None;
+        self.lost = true;
+        self.delay = RECONNECT_DELAY;
+        self.next_attempt = Some(Instant::now() + self.delay);
+        (self.on_event)(UemUsbReconnectEvent::Disconnected);
+    }
+
+    /// Reconnect the lost reader if the time of the next
+    /// attempt has come, otherwise fail without touching USB
+    fn reconnect_lost(&mut self) -> UemResult {
+        if self.next_attempt.is_some_and(|at| Instant::now() < at) {
+            return Err(UemError::ReaderDisconnected);
+        }
+        match self.reconnect() {
+            Ok(()) => {
+                self.next_attempt = None;
+                Ok(())
+            },
+            Err(e) => {
+                self.delay = (self.delay * 2).min(RECONNECT_DELAY_MAX);
+                self.next_attempt = Some(Instant::now() + self.delay);
+                Err(e)
+            },
+        }
+    }
+}
+
+impl UemReaderInternalTrait for ReaderUsbReconnecting {
+    /// Open USB interface
+    fn open(&mut self) -> UemResult {
+        if self.connected {
+            return Err(UemError::ReaderAlreadyConnected);
+        }
+        if self.reader.is_none() {
+            self.reconnect()?;
+            self.next_attempt = None;
+        } else if let Some(usb_reader) = self.reader.as_mut() {
+            usb_reader.open()?;
+        }
+        self.connected = true;
+        Ok(())
+    }
+
+    /// Close opened USB interface
+    fn close(&mut self) -> core::result::Result<(), UemError> {
+        if !self.connected {
+            return Err(UemError::ReaderNotConnected);
+        }
+        self.connected = false;
+        match self.reader.as_mut() {
+            Some(usb_reader) => usb_reader.close(),
+            None => Ok(()),
+        }
+    }
+
+    /// Send command to a USB reader, reopening
+    /// it first if it has been disconnected
+    fn send(&mut self, command: &[u8]) -> UemResultVec {
+        if !self.connected {
+            return Err(UemError::ReaderNotConnected);
+        }
+        if self.reader.is_none() {
+            self.reconnect_lost()?;
+        }
+        let usb_reader = self.reader.as_mut().ok_or(UemError::ReaderDisconnected)?;
+        let res = usb_reader.send(command);
+        if let Err(UemError::ReaderDisconnected) = res {
+            self.lost();
+        }
+        res
+    }
+
+    /// Send command without allocating memory,
+    /// unless the reader is reopened
+    fn send_into(&mut self, command: &[u8], response: &mut [u8]) -> UemResultLen {
+        if !self.connected {
+            return Err(UemError::ReaderNotConnected);
+        }
+        if self.reader.is_none() {
+            self.reconnect_lost()?;
+        }
+        let usb_reader = self.reader.as_mut().ok_or(UemError::ReaderDisconnected)?;
+        let res = usb_reader.send_into(command, response);
+        if let Err(UemError::ReaderDisconnected) = res {
+            self.lost();
+        }
+        res
+    }
+
+    /// Set response timeout, it is kept
+    /// across reconnections
+    fn set_timeout(&mut self, timeout: Duration) -> UemResult {
+        if timeout.is_zero() {
+            return Err(UemError::IncorrectParameter);
+        }
+        self.timeout = timeout;
+        match self.reader.as_mut() {
+            Some(usb_reader) => usb_reader.set_timeout(timeout),
+            None => Ok(()),
+        }
+    }
+
+    /// Current response timeout
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Set counter of the next command,
+    /// a reopened reader starts counting anew
+    fn set_commands_count(&mut self, count: u8) -> UemResult {
+        match self.reader.as_mut() {
+            Some(usb_reader) => usb_reader.set_commands_count(count),
+            None => Err(UemError::ReaderNotConnected),
+        }
+    }
+}
+
+/// Open a USB reader with specific serial number
+/// in automatic reconnection mode
+///
+/// If the reader gets unplugged, commands fail with
+/// [`UemError::ReaderDisconnected`](UemError::ReaderDisconnected)
+/// until the reader with the same serial number is plugged
+/// back in. Then it is reopened transparently on the next command.
+/// USB devices are enumerated by commands sent at growing
+/// intervals, from a quarter of a second up to 4 seconds,
+/// other commands fail without touching USB.
+///
+/// # Arguments
+///
+/// * `serial` - Serial number string descriptor of the reader
+/// * `on_event` - Callback receiving [connection changes](UemUsbReconnectEvent)
+///
+/// # Returns
+///
+/// `Ok(UemReader)` with opened reader on success,
+/// [`UemError::IncorrectReaderName`](UemError::IncorrectReaderName)
+/// if there is no such reader, otherwise returns an error.
+///
+/// # Example
+///
+/// ```no_run
+/// # use uem_reader::reader::usb::reconnect::*;
+/// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+/// let uem_reader = open_usb_reader_reconnecting("0123456789", |event| {
+///     println!("Reader connection changed: {:?}", event);
+/// });
+///
+/// if uem_reader.is_err() {
+///     return;
+/// }
+///
+/// let mut uem_reader = uem_reader.unwrap();
+///
+/// loop {
+///     uem_reader.commands().reader().beep(1).ok();
+///     std::thread::sleep(std::time::Duration::from_secs(1));
+/// }
+/// ```
+pub fn open_usb_reader_reconnecting(serial: &str, on_event: impl FnMut(UemUsbReconnectEvent) + Send + 'static) -> core::result::Result<UemReader, UemError> {
+    let mut usb_reader = ReaderUsbReconnecting {
+        serial: serial.to_string(),
+        filter: UemUsbFilter::default(),
+        reader: None,
+        connected: false,
+        lost: false,
+        timeout: TIMEOUT,
+        delay: RECONNECT_DELAY,
+        next_attempt: None,
+        on_event: Box::new(on_event),
+    };
+    usb_reader.open().map_err(|e| match e {
+        UemError::ReaderDisconnected => UemError::IncorrectReaderName,
+        e => e,
+    })?;
+    Ok(Arc::new(Mutex::new(usb_reader)))
+}
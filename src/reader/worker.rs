@@ -0,0 +1,524 @@
+//! Reader objects served by a dedicated thread
+//!
+//! A [worker](UemReaderWorker) owns a reader and runs jobs
+//! submitted by any number of clients one by one. Jobs wait
+//! in a queue ordered by [priority](UemPriority), jobs of the
+//! same priority run in submission order. A job not started
+//! before its deadline fails as soon as the deadline passes,
+//! even while the worker is busy with another job.
+//!
+//! Every job runs in an [exclusive session](crate::commands::session),
+//! so a sequence of commands in one job is never interrupted.
+//! Results are delivered through [pending results](UemPending),
+//! which can be waited for by a thread or awaited by a task.
+//!
+//! # Example
+//!
+//! ```
+//! # use uem_reader::reader::{mock::UemReaderMock, worker::*};
+//! # use uem_reader::commands::reader::UemColor;
+//! # let uem_mock = UemReaderMock::new();
+//! # uem_mock.expect(&[0x07, 0x02, 0x01, 0x00], &[]);
+//! # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+//! let uem_worker = UemReaderWorker::new(uem_mock.reader());
+//! # let (open_gate, gate) = std::sync::mpsc::channel::<()>();
+//! # let busy = uem_worker.submit(&UemJobParameters::default(), move |_| Ok(gate.recv().ok()));
+//!
+//! // Feedback to a user should not wait for card polling
+//! let serial = uem_worker.commands().reader().get_serial();
+//! let led = uem_worker.commands()
+//!     .with_priority(UemPriority::High)
+//!     .reader().led(1, UemColor::Green, UemColor::Off);
+//! # open_gate.send(()).unwrap();
+//! # busy.wait().unwrap();
+//!
+//! assert!(led.wait().is_ok());
+//! assert_eq!(serial.wait().unwrap(), vec![0x01, 0x02, 0x03, 0x04]);
+//! # drop(uem_worker);
+//! # uem_mock.assert_done();
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::reader::*;
+use crate::commands::{session::*, worker::*};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Priority of a job in a worker queue
+pub enum UemPriority {
+    /// Background activity, e.g. health checks
+    Low,
+    /// Regular commands, e.g. card polling
+    #[default]
+    Normal,
+    /// Time critical commands, e.g. user feedback
+    High,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Queueing parameters of a job
+///
+/// # Example
+///
+/// ```
+/// # use std::time::{Duration, Instant};
+/// # use uem_reader::errors::UemError;
+/// # use uem_reader::reader::{mock::UemReaderMock, worker::*};
+/// # let uem_mock = UemReaderMock::new();
+/// # let uem_worker = UemReaderWorker::new(uem_mock.reader());
+/// # let (open_gate, gate) = std::sync::mpsc::channel::<()>();
+/// # let busy = uem_worker.submit(&UemJobParameters::default(), move |_| Ok(gate.recv().ok()));
+/// // The worker is busy with a long job
+/// let version = uem_worker.submit(&UemJobParameters {
+///     deadline: Some(Instant::now() + Duration::from_millis(50)),
+///     ..Default::default()
+/// }, |session| session.send(&[0x64]));
+///
+/// assert!(matches!(version.wait(), Err(UemError::ReaderTimeout)));
+/// # open_gate.send(()).unwrap();
+/// # busy.wait().unwrap();
+/// # drop(uem_worker);
+/// # uem_mock.assert_done();
+/// ```
+pub struct UemJobParameters {
+    /// Position of the job in the queue
+    pub priority: UemPriority,
+    /// Time by which the job should be started,
+    /// otherwise it fails with
+    /// [`UemError::ReaderTimeout`](UemError::ReaderTimeout).
+    /// Response timeout of the reader is limited by the
+    /// time left when the job starts.
+    pub deadline: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct PendingState<T> {
+    result: Option<UemGeneralResult<T>>,
+    waker: Option<Waker>,
+}
+
+type PendingShared<T> = Arc<(Mutex<PendingState<T>>, Condvar)>;
+
+/// Result of a job which may not be completed yet
+///
+/// The result can be waited for by a thread with
+/// [`wait`](UemPending::wait), or awaited by an
+/// asynchronous task of any runtime.
+///
+/// # Example
+///
+/// ```
+/// # use std::time::Duration;
+/// # use uem_reader::reader::{mock::UemReaderMock, worker::*};
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x64], &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+/// # let uem_worker = UemReaderWorker::new(uem_mock.reader());
+/// let mut version = uem_worker.commands().reader().get_version();
+/// let version = loop {
+///     match version.wait_timeout(Duration::from_millis(10)) {
+///         Ok(version) => break version,
+///         // Do something useful meanwhile
+///         Err(pending) => version = pending,
+///     }
+/// };
+/// # assert_eq!(version.unwrap().len(), 6);
+/// # drop(uem_worker);
+/// # uem_mock.assert_done();
+/// ```
+#[derive(Debug)]
+pub struct UemPending<T> {
+    shared: PendingShared<T>,
+}
+
+impl<T> UemPending<T> {
+    fn new() -> (Self, Completion<T>) {
+        let shared: PendingShared<T> = Arc::new((
+            Mutex::new(PendingState { result: None, waker: None }),
+            Condvar::new(),
+        ));
+        (UemPending { shared: shared.clone() }, Completion { shared: Some(shared) })
+    }
+
+    /// Check if the job has been completed
+    pub fn is_ready(&self) -> bool {
+        self.shared.0.lock().unwrap_or_else(|e| e.into_inner()).result.is_some()
+    }
+
+    /// Block the thread until the job is completed
+    pub fn wait(self) -> UemGeneralResult<T> {
+        let (state, completed) = &*self.shared;
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            state = completed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Block the thread until the job is completed
+    /// or `timeout` is over
+    ///
+    /// # Returns
+    ///
+    /// Job result, or `Err(self)` to wait further
+    /// if the job has not been completed in time.
+    pub fn wait_timeout(self, timeout: Duration) -> core::result::Result<UemGeneralResult<T>, Self> {
+        let (state, completed) = &*self.shared;
+        let (mut state, _) = completed
+            .wait_timeout_while(state.lock().unwrap_or_else(|e| e.into_inner()), timeout, |s| s.result.is_none())
+            .unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(result) => Ok(result),
+            None => {
+                drop(state);
+                Err(self)
+            },
+        }
+    }
+}
+
+impl<T> Future for UemPending<T> {
+    type Output = UemGeneralResult<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.0.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+/// Sending side of a pending result, a job dropped
+/// without completion fails with
+/// [`UemError::Unexpected`](UemError::Unexpected)
+struct Completion<T> {
+    shared: Option<PendingShared<T>>,
+}
+
+impl<T> Completion<T> {
+    fn complete(mut self, result: UemGeneralResult<T>) {
+        if let Some(shared) = self.shared.take() {
+            Self::deliver(&shared, result);
+        }
+    }
+
+    fn deliver(shared: &PendingShared<T>, result: UemGeneralResult<T>) {
+        let (state, completed) = &**shared;
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        completed.notify_all();
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            Self::deliver(&shared, Err(UemError::Unexpected));
+        }
+    }
+}
+
+/// Type erased job, called with an error
+/// instead of a session if it cannot be run
+type Job = Box<dyn FnOnce(UemGeneralResult<&mut UemSession<'_>>) + Send>;
+
+struct QueuedJob {
+    priority: UemPriority,
+    sequence: u64,
+    deadline: Option<Instant>,
+    job: Job,
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Higher priority first, then earlier submission
+        self.priority.cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedJob {}
+
+#[derive(Default)]
+struct QueueState {
+    jobs: BinaryHeap<QueuedJob>,
+    sequence: u64,
+    stopped: bool,
+}
+
+impl QueueState {
+    /// Remove jobs with deadlines passed by `now`
+    fn take_expired(&mut self, now: Instant) -> Vec<QueuedJob> {
+        let expired = |queued: &QueuedJob| queued.deadline.is_some_and(|deadline| now >= deadline);
+        if !self.jobs.iter().any(expired) {
+            return vec![];
+        }
+        let (expired, jobs): (Vec<_>, Vec<_>) = std::mem::take(&mut self.jobs).into_iter().partition(expired);
+        self.jobs = jobs.into();
+        expired
+    }
+
+    /// Earliest deadline of queued jobs
+    fn next_deadline(&self) -> Option<Instant> {
+        self.jobs.iter().filter_map(|queued| queued.deadline).min()
+    }
+}
+
+#[derive(Default)]
+struct JobQueue {
+    state: Mutex<QueueState>,
+    available: Condvar,
+}
+
+impl JobQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for the next job, `None` once the worker is stopped
+    fn next(&self) -> Option<QueuedJob> {
+        let mut state = self.lock();
+        loop {
+            if state.stopped {
+                return None;
+            }
+            if let Some(job) = state.jobs.pop() {
+                return Some(job);
+            }
+            state = self.available.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Fail queued jobs with
+    /// [`UemError::ReaderTimeout`](UemError::ReaderTimeout)
+    /// once their deadlines pass, until the worker is stopped
+    fn expire(&self) {
+        let mut state = self.lock();
+        while !state.stopped {
+            let now = Instant::now();
+            let expired = state.take_expired(now);
+            if !expired.is_empty() {
+                drop(state);
+                for queued in expired {
+                    (queued.job)(Err(UemError::ReaderTimeout));
+                }
+                state = self.lock();
+                continue;
+            }
+            state = match state.next_deadline() {
+                Some(deadline) => self.available
+                    .wait_timeout(state, deadline.saturating_duration_since(now))
+                    .map(|(state, _)| state)
+                    .unwrap_or_else(|e| e.into_inner().0),
+                None => self.available.wait(state).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+}
+
+/// Reader object served by a dedicated thread
+///
+/// Clients on any thread submit jobs with
+/// [`submit`](UemReaderWorker::submit) or through
+/// [command groups](UemCommandsWorker), the worker can be
+/// shared between threads by reference or in an `Arc`.
+/// Dropping the worker finishes the running job,
+/// fails queued jobs with
+/// [`UemError::ReaderNotConnected`](UemError::ReaderNotConnected)
+/// and stops the thread.
+///
+/// A job must not wait for results of other jobs
+/// of the same worker, it would wait forever.
+///
+/// # Example
+///
+/// ```
+/// # use uem_reader::errors::UemError;
+/// # use uem_reader::reader::{mock::UemReaderMock, worker::*};
+/// # let uem_mock = UemReaderMock::new();
+/// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+/// let uem_worker = UemReaderWorker::new(uem_mock.reader());
+///
+/// // A panicking job fails alone
+/// let broken = uem_worker.submit(&UemJobParameters::default(), |_| -> Result<(), UemError> {
+///     panic!("broken job")
+/// });
+/// assert!(matches!(broken.wait(), Err(UemError::Unexpected)));
+///
+/// let serial = uem_worker.commands().reader().get_serial();
+/// assert_eq!(serial.wait().unwrap(), vec![0x01, 0x02, 0x03, 0x04]);
+/// # drop(uem_worker);
+/// # uem_mock.assert_done();
+/// ```
+pub struct UemReaderWorker {
+    queue: Arc<JobQueue>,
+    thread: Option<JoinHandle<()>>,
+    deadlines: Option<JoinHandle<()>>,
+}
+
+impl UemReaderWorker {
+    /// Start a thread serving the `reader`
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A reader object created by any of the transports,
+    ///   it should not be used by other means while the worker runs
+    pub fn new(reader: UemReader) -> Self {
+        let queue = Arc::new(JobQueue::default());
+        let worker_queue = queue.clone();
+        let thread = std::thread::Builder::new()
+            .name("uem-reader-worker".to_string())
+            .spawn(move || Self::serve(reader, &worker_queue))
+            .ok();
+        let deadlines_queue = queue.clone();
+        let deadlines = std::thread::Builder::new()
+            .name("uem-reader-deadlines".to_string())
+            .spawn(move || deadlines_queue.expire())
+            .ok();
+        UemReaderWorker { queue, thread, deadlines }
+    }
+
+    fn serve(reader: UemReader, queue: &JobQueue) {
+        while let Some(queued) = queue.next() {
+            // Readers reject a zero timeout, so a job
+            // with no time left is never started
+            let left = queued.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if left == Some(Duration::ZERO) {
+                (queued.job)(Err(UemError::ReaderTimeout));
+                continue;
+            }
+            match reader.session() {
+                Ok(mut session) => {
+                    let default_timeout = session.timeout();
+                    if let Some(left) = left {
+                        session.set_timeout(left.min(default_timeout)).ok();
+                    }
+                    // A panicking job fails alone, its result
+                    // is completed with an error when dropped.
+                    // The session outlives the unwinding,
+                    // so the reader is not poisoned by the job.
+                    let job = queued.job;
+                    catch_unwind(AssertUnwindSafe(|| job(Ok(&mut session)))).ok();
+                    session.set_timeout(default_timeout).ok();
+                },
+                Err(e) => (queued.job)(Err(e)),
+            }
+        }
+    }
+
+    /// Get commands object of the worker
+    pub fn commands(&self) -> UemCommandsWorker<'_> {
+        UemCommandsWorker::new(self)
+    }
+
+    /// Queue a `job` running in an exclusive session
+    ///
+    /// # Returns
+    ///
+    /// [Pending result](UemPending) of the job. It fails with
+    /// [`UemError::ReaderTimeout`](UemError::ReaderTimeout)
+    /// once its deadline passes before the job is started,
+    /// [`UemError::ReaderNotConnected`](UemError::ReaderNotConnected)
+    /// if the worker has been stopped, or
+    /// [`UemError::Unexpected`](UemError::Unexpected)
+    /// if the job has panicked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::time::{Duration, Instant};
+    /// # use uem_reader::reader::{mock::UemReaderMock, worker::*};
+    /// # use uem_reader::commands::{UemCommandsTrait, reader::*};
+    /// # let uem_mock = UemReaderMock::new();
+    /// # uem_mock.expect(&[0x64], &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    /// # uem_mock.expect(&[0x22], &[0x01, 0x02, 0x03, 0x04]);
+    /// # let uem_worker = UemReaderWorker::new(uem_mock.reader());
+    /// let identity = uem_worker.submit(&UemJobParameters {
+    ///     deadline: Some(Instant::now() + Duration::from_secs(1)),
+    ///     ..Default::default()
+    /// }, |session| {
+    ///     let version = session.commands().reader().get_version()?;
+    ///     let serial = session.commands().reader().get_serial()?;
+    ///     Ok((version, serial))
+    /// });
+    /// # assert!(identity.wait().is_ok());
+    /// # drop(uem_worker);
+    /// # uem_mock.assert_done();
+    /// ```
+    pub fn submit<T, F>(&self, parameters: &UemJobParameters, job: F) -> UemPending<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut UemSession<'_>) -> UemGeneralResult<T> + Send + 'static,
+    {
+        let (pending, completion) = UemPending::new();
+        let job: Job = Box::new(move |session| completion.complete(session.and_then(job)));
+
+        let mut state = self.queue.lock();
+        if state.stopped || self.thread.is_none() {
+            drop(state);
+            job(Err(UemError::ReaderNotConnected));
+            return pending;
+        }
+        state.sequence += 1;
+        let sequence = state.sequence;
+        state.jobs.push(QueuedJob {
+            priority: parameters.priority,
+            sequence,
+            deadline: parameters.deadline,
+            job,
+        });
+        // Wake both the serving and the deadline threads
+        self.queue.available.notify_all();
+        pending
+    }
+
+    /// Number of jobs waiting in the queue
+    pub fn queued(&self) -> usize {
+        self.queue.lock().jobs.len()
+    }
+}
+
+impl Drop for UemReaderWorker {
+    fn drop(&mut self) {
+        let jobs = {
+            let mut state = self.queue.lock();
+            state.stopped = true;
+            std::mem::take(&mut state.jobs)
+        };
+        self.queue.available.notify_all();
+        for queued in jobs {
+            (queued.job)(Err(UemError::ReaderNotConnected));
+        }
+        for thread in [self.thread.take(), self.deadlines.take()].into_iter().flatten() {
+            thread.join().ok();
+        }
+    }
+}